@@ -2,9 +2,10 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::*;
 
-use rand::prelude::*;
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 pub enum Plane {
@@ -124,7 +125,7 @@ impl<M: Sync + Send + Material + 'static> Hittable for AARect<M> {
 
     fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
         if let Some(hit) = self.hit(&Ray::new(orig, v, 0.0), 0.001, std::f32::INFINITY) {
-            let area = (self.a1 - self.a0) * (self.b1 - self.b0);
+            let area = self.area();
 
             let distance_squared = hit.t.powi(2) * v.length_squared();
 
@@ -136,28 +137,26 @@ impl<M: Sync + Send + Material + 'static> Hittable for AARect<M> {
         0.0
     }
 
-    fn random(&self, orig: Vec3) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u, v) = sampler.get_2d();
+        let a = self.a0 + u * (self.a1 - self.a0);
+        let b = self.b0 + v * (self.b1 - self.b0);
         let random_point = match &self.plane {
-            Plane::XY => Point3::new(
-                rng.gen_range(self.a0..self.a1),
-                rng.gen_range(self.b0..self.b1),
-                self.k,
-            ),
-            Plane::XZ => Point3::new(
-                rng.gen_range(self.a0..self.a1),
-                self.k,
-                rng.gen_range(self.b0..self.b1),
-            ),
-            Plane::YZ => Point3::new(
-                self.k,
-                rng.gen_range(self.a0..self.a1),
-                rng.gen_range(self.b0..self.b1),
-            ),
+            Plane::XY => Point3::new(a, b, self.k),
+            Plane::XZ => Point3::new(a, self.k, b),
+            Plane::YZ => Point3::new(self.k, a, b),
         };
 
         random_point - orig
     }
+
+    fn is_area_light(&self) -> bool {
+        self.material.is_emitter()
+    }
+
+    fn area(&self) -> f32 {
+        (self.a1 - self.a0) * (self.b1 - self.b0)
+    }
 }
 
 pub struct RectBox {
@@ -240,4 +239,7 @@ impl Hittable for RectBox {
     fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
         Some(AABB::new(self.box_min, self.box_max))
     }
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        self.sides.area_lights(lights)
+    }
 }
@@ -0,0 +1,97 @@
+use crate::film::Film;
+use crate::vec3::*;
+
+// Adaptive sampling: every pixel first gets `min_spp` samples, then passes of `step` more
+// samples go only to the pixels whose relative error (in a 3x3 neighbourhood, so a pixel that
+// got lucky with its first samples isn't left behind) is still above `threshold`, up to
+// `max_spp` per pixel. The total stays within roughly `budget` samples, so whatever flat
+// regions don't use goes to the noisy ones.
+#[derive(Debug, Clone)]
+pub struct Adaptive {
+    pub threshold: f32,
+    pub min_spp: u32,
+    pub max_spp: u32,
+    pub step: u32,
+    pub budget: u64,
+}
+
+impl Adaptive {
+    // Samples to add to every pixel in the next pass, or None once the image has converged or
+    // the budget is spent.
+    pub fn plan(&self, film: &Film) -> Option<Vec<u32>> {
+        if film.samples_taken() >= self.budget {
+            return None;
+        }
+
+        let errors: Vec<f32> = film.pixels().iter().map(|p| p.relative_error()).collect();
+        let mut plan = vec![0; errors.len()];
+        let mut any = false;
+
+        for row in 0..film.height {
+            for x in 0..film.width {
+                let samples = film.pixel(x, row).samples;
+                let count = if samples < self.min_spp {
+                    self.min_spp - samples
+                } else if samples < self.max_spp
+                    && neighbourhood_error(&errors, film.width, film.height, x, row)
+                        > self.threshold
+                {
+                    self.step.min(self.max_spp - samples)
+                } else {
+                    0
+                };
+
+                plan[row * film.width + x] = count;
+                any |= count > 0;
+            }
+        }
+
+        if any {
+            Some(plan)
+        } else {
+            None
+        }
+    }
+}
+
+fn neighbourhood_error(errors: &[f32], width: usize, height: usize, x: usize, row: usize) -> f32 {
+    let mut max = 0.0f32;
+    for r in row.saturating_sub(1)..(row + 2).min(height) {
+        for c in x.saturating_sub(1)..(x + 2).min(width) {
+            max = max.max(errors[r * width + c]);
+        }
+    }
+    max
+}
+
+// Sample count of every pixel as a blue (fewest) to red (most) ramp, for checking where the
+// adaptive sampler spent its budget.
+pub fn heatmap(film: &Film) -> Vec<Color> {
+    let max = film
+        .pixels()
+        .iter()
+        .map(|p| p.samples)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    film.pixels()
+        .iter()
+        .map(|p| ramp(p.samples as f32 / max as f32))
+        .collect()
+}
+
+// blue (0) -> cyan -> green -> yellow -> red (1)
+pub fn ramp(t: f32) -> Color {
+    let c = if t < 0.25 {
+        Color::new(0.0, t * 4.0, 1.0)
+    } else if t < 0.5 {
+        Color::new(0.0, 1.0, 1.0 - (t - 0.25) * 4.0)
+    } else if t < 0.75 {
+        Color::new((t - 0.5) * 4.0, 1.0, 0.0)
+    } else {
+        Color::new(1.0, 1.0 - (t - 0.75) * 4.0, 0.0)
+    };
+    // the 8 bit writers gamma correct, so square to keep the ramp as is
+    c * c
+}
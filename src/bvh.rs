@@ -2,9 +2,19 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::ray::Ray;
 
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::sync::Arc;
 
+thread_local! {
+    static NODES_VISITED: Cell<u64> = const { Cell::new(0) };
+}
+
+// BVH nodes whose bounds were tested on this thread since the last call
+pub fn take_node_count() -> u64 {
+    NODES_VISITED.with(|c| c.replace(0))
+}
+
 enum BVHNode {
     Branch { left: Arc<BVH>, right: Arc<BVH> },
     Leaf(Arc<dyn Hittable>)
@@ -74,6 +84,7 @@ impl BVH {
 
 impl Hittable for BVH {
     fn hit(&self, r: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord> {
+        NODES_VISITED.with(|c| c.set(c.get() + 1));
         if self.bbox.hit(&r, t_min, t_max) {
             match &self.tree {
                 BVHNode::Leaf(leaf) => leaf.hit(&r, t_min, t_max),
@@ -92,4 +103,15 @@ impl Hittable for BVH {
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         Some(self.bbox.clone())
     }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        match &self.tree {
+            BVHNode::Leaf(leaf) if leaf.is_area_light() => lights.push(leaf.clone()),
+            BVHNode::Leaf(leaf) => leaf.area_lights(lights),
+            BVHNode::Branch { left, right } => {
+                left.area_lights(lights);
+                right.area_lights(lights);
+            }
+        }
+    }
 }
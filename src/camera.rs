@@ -1,6 +1,6 @@
 use crate::ray::Ray;
+use crate::sampler::{self, Sampler};
 use crate::vec3::*;
-use rand::prelude::*;
 
 pub struct Camera {
     pub origin: Point3,
@@ -58,16 +58,17 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
+    pub fn get_ray(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let lens = sampler.get_2d();
         let origin = if self.lens_radius == 0.0 {
             self.origin
         } else {
-            let rd = self.lens_radius * Vec3::random_in_unit_disk();
-            let offset = self.u * rd.x + self.v * rd.y;
+            let (x, y) = sampler::sample_disk(lens);
+            let offset = self.lens_radius * (self.u * x + self.v * y);
             self.origin + offset
         };
 
-        let time = self.time0 + rand::thread_rng().gen::<f32>() * (self.time1 - self.time0);
+        let time = self.time0 + sampler.get_1d() * (self.time1 - self.time0);
         let dir = self.lower_left_corner + s * self.horizontal + t * self.vertical - origin;
 
         Ray::new(origin, dir, time)
@@ -0,0 +1,444 @@
+use crate::adaptive::Adaptive;
+use crate::integrator::IntegratorKind;
+use crate::light_sampler::LightSamplerKind;
+use crate::output::ImageFormat;
+use crate::render::TileOrder;
+use crate::sampler::SamplerKind;
+use crate::scenes;
+
+use std::path::Path;
+
+pub const USAGE: &str = "usage: rei-treicem [options]
+
+options:
+    -s, --scene <name>      scene function from scenes.rs to render (default: cornell_box)
+        --scene-file <path> render a TOML scene description instead of a built-in scene
+    -w, --width <px>        image width in pixels (default: 500)
+    -h, --height <px>       image height in pixels (default: same as width)
+    -n, --spp <n>           samples per pixel (default: 100)
+    -d, --max-depth <n>     most bounces a path can take (default: 50)
+        --max-diffuse <n>   most diffuse bounces a path can take (default: max depth)
+        --max-specular <n>  most mirror and glass bounces (default: max depth)
+        --max-volume <n>    most scattering events in smoke and fog (default: max depth)
+        --rr-depth <n>      bounces before Russian roulette starts ending paths that
+                            carry little light (default: 3)
+        --sampler <name>    sample sequence (supported: random, stratified, halton, sobol,
+                            bluenoise; default: sobol)
+        --integrator <name> light transport algorithm (supported: path, whitted, ao,
+                            direct, normals, depth, uv, bvh-cost, material-id;
+                            default: path). whitted is a quick preview, the last five
+                            show a property of the surface each pixel sees
+        --light-sampler <name>
+                            how shadow rays pick a light (supported: uniform, power,
+                            tree; default: tree). power favours bright lights, tree
+                            also favours close ones, for scenes with many lights
+        --seed <n>          seed of every random choice. the same seed, scene and
+                            settings give the same image, whatever the thread count
+                            (default: 0)
+        --spectral          trace every path at a wavelength of its own instead of in RGB,
+                            for blackbody and measured spectra. RGB colours are turned into
+                            smooth spectra. only path, whitted and direct
+    -o, --output <path>     output image path; animated scenes get the frame number
+                            appended to the file name (default: image.ppm)
+    -f, --format <fmt>      output format, inferred from the output extension when
+                            omitted (supported: ppm, png, hdr, exr).
+                            hdr and exr store linear, unclamped radiance
+        --pass-spp <n>      progressive mode: render in passes of n samples per pixel
+                            and write the image so far to the output after each pass
+        --checkpoint <s>    progressive mode: write the image so far every s seconds
+                            instead of after each pass
+        --state <path>      save the accumulated render to <path> after every pass, so
+                            it can be continued later with --resume
+        --resume            continue from the state saved with --state, adding samples
+                            until --spp is reached. refuses states saved for another
+                            scene, resolution or depth
+        --adaptive <err>    adaptive sampling: stop sampling pixels once the relative
+                            standard error of their luminance drops below <err>
+                            (e.g. 0.02), and spend the --spp budget on noisy ones
+        --min-spp <n>       adaptive: samples every pixel gets first (default: spp/4,
+                            at least 4)
+        --max-spp <n>       adaptive: most samples a pixel can get (default: 4 x spp)
+        --sample-heatmap <path>
+                            also write the number of samples of every pixel as a
+                            heatmap image
+        --tile-size <px>    edge length of the square tiles the image is split into
+                            (default: 16)
+        --tile-order <ord>  order tiles are rendered in (supported: hilbert, spiral,
+                            scanline; default: hilbert)
+        --list-scenes       print the available scenes and exit
+        --help              print this message and exit";
+
+#[derive(Debug, Clone)]
+pub enum SceneSource {
+    Builtin(String),
+    File(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub scene: SceneSource,
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: i32,
+    pub max_diffuse: i32,
+    pub max_specular: i32,
+    pub max_volume: i32,
+    pub rr_depth: i32,
+    pub sampler: SamplerKind,
+    pub integrator: IntegratorKind,
+    pub light_sampler: LightSamplerKind,
+    pub seed: u32,
+    pub spectral: bool,
+    pub output: String,
+    pub format: ImageFormat,
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    // samples per pixel of each progressive pass
+    pub pass_spp: Option<usize>,
+    // seconds between checkpoint images
+    pub checkpoint_interval: Option<u64>,
+    pub state: Option<String>,
+    pub resume: bool,
+    // relative error threshold, enables adaptive sampling
+    pub adaptive_threshold: Option<f32>,
+    pub min_spp: Option<usize>,
+    pub max_spp: Option<usize>,
+    pub sample_heatmap: Option<String>,
+}
+
+pub enum Command {
+    Render(Box<Options>),
+    ListScenes,
+    Help,
+}
+
+impl Options {
+    pub fn progressive(&self) -> bool {
+        self.pass_spp.is_some() || self.checkpoint_interval.is_some()
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    // file name for a given frame. single frame scenes are written to `output` as is, while
+    // animations get a zero padded frame number before the extension: image.ppm -> image007.ppm
+    pub fn frame_path(&self, frame: usize, frame_count: usize) -> String {
+        numbered_path(&self.output, frame, frame_count)
+    }
+
+    pub fn adaptive(&self) -> Option<Adaptive> {
+        let threshold = self.adaptive_threshold?;
+        let spp = self.samples_per_pixel;
+        let max_spp = self.max_spp.unwrap_or(4 * spp);
+        // the error estimate needs at least two samples
+        let min_spp = self.min_spp.unwrap_or((spp / 4).max(4)).clamp(2, max_spp);
+
+        Some(Adaptive {
+            threshold,
+            min_spp: min_spp as u32,
+            max_spp: max_spp as u32,
+            step: self.pass_spp.unwrap_or((spp / 4).max(1)) as u32,
+            budget: (spp * self.width * self.height) as u64,
+        })
+    }
+
+    pub fn heatmap_path(&self, frame: usize, frame_count: usize) -> Option<String> {
+        self.sample_heatmap
+            .as_ref()
+            .map(|path| numbered_path(path, frame, frame_count))
+    }
+
+    // render state file for a given frame, numbered like the images
+    pub fn state_path(&self, frame: usize, frame_count: usize) -> Option<String> {
+        self.state
+            .as_ref()
+            .map(|path| numbered_path(path, frame, frame_count))
+    }
+}
+
+fn numbered_path(path: &str, frame: usize, frame_count: usize) -> String {
+    if frame_count <= 1 {
+        return path.to_string();
+    }
+
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}{:03}.{}", stem, frame, ext),
+        None => format!("{}{:03}", stem, frame),
+    };
+
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut scene = SceneSource::Builtin("cornell_box".to_string());
+    let mut width = 500;
+    let mut height = None;
+    let mut samples_per_pixel = 100;
+    let mut max_depth = 50;
+    let mut max_diffuse = None;
+    let mut max_specular = None;
+    let mut max_volume = None;
+    let mut rr_depth = 3;
+    let mut sampler = SamplerKind::Sobol;
+    let mut integrator = IntegratorKind::Path;
+    let mut light_sampler = LightSamplerKind::Tree;
+    let mut seed = 0;
+    let mut spectral = false;
+    let mut output = None;
+    let mut format = None;
+    let mut tile_size = 16;
+    let mut tile_order = TileOrder::Hilbert;
+    let mut pass_spp = None;
+    let mut checkpoint_interval = None;
+    let mut state = None;
+    let mut resume = false;
+    let mut adaptive_threshold = None;
+    let mut min_spp = None;
+    let mut max_spp = None;
+    let mut sample_heatmap = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for '{}'", name))
+        };
+
+        match arg.as_str() {
+            "-s" | "--scene" => scene = SceneSource::Builtin(value(&arg)?),
+            "--scene-file" => scene = SceneSource::File(value(&arg)?),
+            "-w" | "--width" => width = parse_positive(&arg, &value(&arg)?)?,
+            "-h" | "--height" => height = Some(parse_positive(&arg, &value(&arg)?)?),
+            "-n" | "--spp" => samples_per_pixel = parse_positive(&arg, &value(&arg)?)?,
+            "-d" | "--max-depth" => max_depth = parse_positive(&arg, &value(&arg)?)? as i32,
+            "--max-diffuse" => max_diffuse = Some(parse_count(&arg, &value(&arg)?)? as i32),
+            "--max-specular" => max_specular = Some(parse_count(&arg, &value(&arg)?)? as i32),
+            "--max-volume" => max_volume = Some(parse_count(&arg, &value(&arg)?)? as i32),
+            "--rr-depth" => rr_depth = parse_count(&arg, &value(&arg)?)? as i32,
+            "--sampler" => {
+                let name = value(&arg)?;
+                sampler = SamplerKind::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown sampler '{}' (supported: {})",
+                        name,
+                        SamplerKind::NAMES
+                    )
+                })?;
+            }
+            "--integrator" => {
+                let name = value(&arg)?;
+                integrator = IntegratorKind::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown integrator '{}' (supported: {})",
+                        name,
+                        IntegratorKind::NAMES
+                    )
+                })?;
+            }
+            "--light-sampler" => {
+                let name = value(&arg)?;
+                light_sampler = LightSamplerKind::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown light sampler '{}' (supported: {})",
+                        name,
+                        LightSamplerKind::NAMES
+                    )
+                })?;
+            }
+            "--seed" => {
+                let v = value(&arg)?;
+                seed = v.parse::<u32>().map_err(|_| {
+                    format!(
+                        "invalid value '{}' for '{}': expected an integer from 0 to {}",
+                        v,
+                        arg,
+                        u32::MAX
+                    )
+                })?;
+            }
+            "--spectral" => spectral = true,
+            "-o" | "--output" => output = Some(value(&arg)?),
+            "-f" | "--format" => {
+                let name = value(&arg)?;
+                format = Some(ImageFormat::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unsupported output format '{}' (supported: {})",
+                        name,
+                        ImageFormat::NAMES
+                    )
+                })?);
+            }
+            "--pass-spp" => pass_spp = Some(parse_positive(&arg, &value(&arg)?)?),
+            "--checkpoint" => {
+                checkpoint_interval = Some(parse_positive(&arg, &value(&arg)?)? as u64)
+            }
+            "--state" => state = Some(value(&arg)?),
+            "--resume" => resume = true,
+            "--adaptive" => adaptive_threshold = Some(parse_threshold(&arg, &value(&arg)?)?),
+            "--min-spp" => min_spp = Some(parse_positive(&arg, &value(&arg)?)?),
+            "--max-spp" => max_spp = Some(parse_positive(&arg, &value(&arg)?)?),
+            "--sample-heatmap" => sample_heatmap = Some(value(&arg)?),
+            "--tile-size" => tile_size = parse_positive(&arg, &value(&arg)?)?,
+            "--tile-order" => {
+                let name = value(&arg)?;
+                tile_order = TileOrder::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown tile order '{}' (supported: {})",
+                        name,
+                        TileOrder::NAMES
+                    )
+                })?;
+            }
+            "--list-scenes" => return Ok(Command::ListScenes),
+            "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    if let SceneSource::Builtin(name) = &scene {
+        if scenes::by_name(name).is_none() {
+            return Err(format!(
+                "unknown scene '{}' (available: {})",
+                name,
+                scenes::names().join(", ")
+            ));
+        }
+    }
+
+    if resume && state.is_none() {
+        return Err("--resume needs the state file given with --state".to_string());
+    }
+
+    let format = match (format, &output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => {
+            let ext = Path::new(path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .ok_or_else(|| {
+                    format!("can't infer the image format of '{}', use --format", path)
+                })?;
+            ImageFormat::from_path(path).ok_or_else(|| {
+                format!(
+                    "unsupported output format '{}' (supported: {})",
+                    ext,
+                    ImageFormat::NAMES
+                )
+            })?
+        }
+        (None, None) => ImageFormat::Ppm,
+    };
+
+    let output = output.unwrap_or_else(|| format!("image.{}", format.extension()));
+
+    Ok(Command::Render(Box::new(Options {
+        scene,
+        width,
+        height: height.unwrap_or(width),
+        samples_per_pixel,
+        max_depth,
+        max_diffuse: max_diffuse.unwrap_or(max_depth),
+        max_specular: max_specular.unwrap_or(max_depth),
+        max_volume: max_volume.unwrap_or(max_depth),
+        rr_depth,
+        sampler,
+        integrator,
+        light_sampler,
+        seed,
+        spectral,
+        output,
+        format,
+        tile_size,
+        tile_order,
+        pass_spp,
+        checkpoint_interval,
+        state,
+        resume,
+        adaptive_threshold,
+        min_spp,
+        max_spp,
+        sample_heatmap,
+    })))
+}
+
+fn parse_threshold(name: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive number",
+            value, name
+        )),
+    }
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n <= i32::MAX as usize => Ok(n),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a non-negative integer",
+            value, name
+        )),
+    }
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 && n <= i32::MAX as usize => Ok(n),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive integer",
+            value, name
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(args: &[&str]) -> String {
+        match parse_args(args.iter().map(|a| a.to_string())) {
+            Ok(_) => panic!("accepted {:?}", args),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        for (option, name, what) in [
+            ("--sampler", "lattice", "sampler"),
+            ("--integrator", "photon", "integrator"),
+            ("--light-sampler", "nearest", "light sampler"),
+        ] {
+            let e = error(&[option, name]);
+            let expected = format!("unknown {} '{}' (supported: ", what, name);
+            assert!(e.starts_with(&expected), "{}", e);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_counts() {
+        for (args, value, name) in [
+            (["-w", "0"], "0", "-w"),
+            (["--width", "wide"], "wide", "--width"),
+            (["-n", "0"], "0", "-n"),
+            (["--spp", "-4"], "-4", "--spp"),
+        ] {
+            assert_eq!(
+                error(&args),
+                format!(
+                    "invalid value '{}' for '{}': expected a positive integer",
+                    value, name
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_value() {
+        assert_eq!(error(&["--spp"]), "missing value for '--spp'");
+    }
+}
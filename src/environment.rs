@@ -0,0 +1,248 @@
+use crate::light::{Light, LightSample};
+use crate::sampler::Sampler;
+use crate::spectrum;
+use crate::vec3::*;
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Light from an equirectangular image wrapped around the scene: the top row is straight up,
+// the middle of the image is towards -z, and u grows turning from -z towards +x.
+//
+// Directions are picked in proportion to the luminance of the pixels they look at, so a small
+// bright sun in a sky image gets most of the shadow rays instead of being found by chance.
+pub struct EnvironmentLight {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    // turn around the y axis, in radians
+    cos_rotation: f32,
+    sin_rotation: f32,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    // Radiance .hdr files are read as they are. Other images are 8 bit with the gamma of the
+    // images the renderer writes, and are made linear first. `intensity` scales the image and
+    // `rotation` turns it around the y axis, in degrees.
+    pub fn open(path: &str, intensity: f32, rotation: f32) -> image::ImageResult<Self> {
+        let is_hdr = Path::new(path)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("hdr"));
+
+        let (width, height, pixels) = if is_hdr {
+            let decoder = image::hdr::HDRDecoder::new(BufReader::new(File::open(path)?))?;
+            let meta = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|p| Color::new(p.data[0], p.data[1], p.data[2]))
+                .collect();
+            (meta.width as usize, meta.height as usize, pixels)
+        } else {
+            let image = image::open(path)?.to_rgb();
+            let (width, height) = image.dimensions();
+            let pixels = image
+                .pixels()
+                .map(|p| {
+                    let channel = |c: u8| (c as f32 / 255.0).powi(2);
+                    Color::new(channel(p.data[0]), channel(p.data[1]), channel(p.data[2]))
+                })
+                .collect();
+            (width as usize, height as usize, pixels)
+        };
+
+        if width == 0 || height == 0 {
+            return Err(image::ImageError::DimensionError);
+        }
+        Ok(Self::new(width, height, pixels, intensity, rotation))
+    }
+
+    pub fn new(
+        width: usize,
+        height: usize,
+        mut pixels: Vec<Color>,
+        intensity: f32,
+        rotation: f32,
+    ) -> Self {
+        for p in &mut pixels {
+            *p = intensity * *p;
+        }
+
+        // rows near the poles cover less of the sphere
+        let weights: Vec<Vec<f32>> = (0..height)
+            .map(|y| {
+                let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+                (0..width)
+                    .map(|x| pixels[y * width + x].luminance().max(0.0) * sin_theta)
+                    .collect()
+            })
+            .collect();
+
+        let rotation = rotation.to_radians();
+        Self {
+            width,
+            height,
+            pixels,
+            cos_rotation: rotation.cos(),
+            sin_rotation: rotation.sin(),
+            distribution: Distribution2D::new(&weights),
+        }
+    }
+
+    // image coordinates, in 0..1, of a world direction
+    fn to_uv(&self, dir: Vec3) -> (f32, f32) {
+        let d = dir.unit_vector();
+        // undo the rotation
+        let x = self.cos_rotation * d.x + self.sin_rotation * d.z;
+        let z = -self.sin_rotation * d.x + self.cos_rotation * d.z;
+        let phi = x.atan2(-z);
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        (0.5 + phi / (2.0 * PI), theta / PI)
+    }
+
+    // world direction of image coordinates, and the sine of its angle from straight up
+    fn direction(&self, u: f32, v: f32) -> (Vec3, f32) {
+        let phi = 2.0 * PI * (u - 0.5);
+        let theta = PI * v;
+        let sin_theta = theta.sin();
+        let x = sin_theta * phi.sin();
+        let z = -sin_theta * phi.cos();
+        let dir = Vec3::new(
+            self.cos_rotation * x - self.sin_rotation * z,
+            theta.cos(),
+            self.sin_rotation * x + self.cos_rotation * z,
+        );
+        (dir, sin_theta)
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Color {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        spectrum::uplift(self.pixels[y * self.width + x])
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _p: Point3, _time: f32, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let ((u, v), pdf_uv) = self.distribution.sample(sampler.get_2d());
+        let (dir, sin_theta) = self.direction(u, v);
+        if pdf_uv <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            dir,
+            distance: f32::INFINITY,
+            radiance: self.lookup(u, v),
+            // from the image's unit square to the sphere
+            pdf: pdf_uv / (2.0 * PI * PI * sin_theta),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _p: Point3, dir: Vec3) -> f32 {
+        let (u, v) = self.to_uv(dir);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn escaped(&self, dir: Vec3) -> Color {
+        let (u, v) = self.to_uv(dir);
+        self.lookup(u, v)
+    }
+
+    fn power(&self, scene_radius: f32) -> f32 {
+        let mean =
+            self.pixels.iter().map(|p| p.luminance()).sum::<f32>() / self.pixels.len() as f32;
+        PI * scene_radius * scene_radius * mean
+    }
+}
+
+// Piecewise constant density over 0..1, one piece per value of `func`.
+struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f32;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            // nothing to go by, pick uniformly
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f32 / n as f32
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    // a point in 0..1, its density and the piece it's in
+    fn sample(&self, u: f32) -> (f32, f32, usize) {
+        let n = self.func.len();
+        let i = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+        ((i as f32 + du) / n as f32, self.pdf(i), i)
+    }
+
+    fn pdf(&self, i: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[i] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// Piecewise constant density over the unit square, picking a row and then a column in it.
+struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    fn new(weights: &[Vec<f32>]) -> Self {
+        let rows: Vec<Distribution1D> = weights
+            .iter()
+            .map(|row| Distribution1D::new(row.clone()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|r| r.integral).collect());
+        Self { rows, marginal }
+    }
+
+    fn sample(&self, u: (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample(u.1);
+        let (u, pdf_u, _) = self.rows[row].sample(u.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    fn pdf(&self, u: f32, v: f32) -> f32 {
+        let row = ((v * self.rows.len() as f32) as usize).min(self.rows.len() - 1);
+        let columns = &self.rows[row];
+        let column = ((u * columns.func.len() as f32) as usize).min(columns.func.len() - 1);
+        columns.pdf(column) * self.marginal.pdf(row)
+    }
+}
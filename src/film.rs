@@ -0,0 +1,174 @@
+use crate::output::ImageFormat;
+use crate::spectrum;
+use crate::vec3::*;
+
+use std::io;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pixel {
+    // weighted sum of the linear radiance of every sample
+    pub sum: Color,
+    pub weight: f32,
+    pub samples: u32,
+    // running mean and sum of squared deviations of the sample luminance (Welford), for the
+    // error estimate of adaptive sampling
+    pub lum_mean: f32,
+    pub lum_m2: f32,
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pixel {
+    pub fn new() -> Self {
+        Self {
+            sum: Color::new_empty(),
+            weight: 0.0,
+            samples: 0,
+            lum_mean: 0.0,
+            lum_m2: 0.0,
+        }
+    }
+
+    pub fn add_sample(&mut self, radiance: Color, weight: f32) {
+        self.sum += radiance * weight;
+        self.weight += weight;
+        self.samples += 1;
+
+        let lum = radiance.luminance();
+        let n = self.samples as f32;
+        let delta = lum - self.lum_mean;
+        self.lum_mean += delta / n;
+        self.lum_m2 += delta * (lum - self.lum_mean);
+    }
+
+    // a sample of a path that followed a single wavelength, in nanometers, with `radiance` the
+    // light of that wavelength as if it were white
+    pub fn add_spectral_sample(&mut self, radiance: Color, lambda: f32, weight: f32) {
+        self.add_sample(radiance * spectrum::wavelength_weight(lambda), weight);
+    }
+
+    // combines the samples of two pixels, as if they had all been added to one
+    pub fn merge(&mut self, other: &Pixel) {
+        let n = self.samples + other.samples;
+        if n > 0 {
+            let (na, nb) = (self.samples as f32, other.samples as f32);
+            let delta = other.lum_mean - self.lum_mean;
+            self.lum_mean += delta * nb / n as f32;
+            self.lum_m2 += other.lum_m2 + delta * delta * na * nb / n as f32;
+        }
+
+        self.sum += other.sum;
+        self.weight += other.weight;
+        self.samples = n;
+    }
+
+    // standard error of the mean luminance relative to the mean. the small constant keeps
+    // noise in nearly black pixels from counting as huge relative errors
+    pub fn relative_error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let n = self.samples as f32;
+        let variance = self.lum_m2 / (n - 1.0);
+        (variance / n).sqrt() / (self.lum_mean.abs() + 0.01)
+    }
+
+    pub fn color(&self) -> Color {
+        if self.weight > 0.0 {
+            self.sum / self.weight
+        } else {
+            Color::new_empty()
+        }
+    }
+}
+
+// Accumulation buffer for a frame. Pixels are stored row by row, with row 0 at the top of the
+// image.
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Pixel::new(); width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, row: usize) -> &Pixel {
+        &self.pixels[row * self.width + x]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, row: usize) -> &mut Pixel {
+        &mut self.pixels[row * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.pixels
+    }
+
+    // total number of samples in the film
+    pub fn samples_taken(&self) -> u64 {
+        self.pixels.iter().map(|p| p.samples as u64).sum()
+    }
+
+    // adds a tile rendered by a worker thread to the frame
+    pub fn merge_tile(&mut self, tile: &FilmTile) {
+        for row in 0..tile.height {
+            for x in 0..tile.width {
+                let src = &tile.pixels[row * tile.width + x];
+                self.pixel_mut(tile.x0 + x, tile.row0 + row).merge(src);
+            }
+        }
+    }
+
+    // the resolved linear radiance of every pixel, top row first
+    pub fn to_image(&self) -> Vec<Color> {
+        self.pixels.iter().map(Pixel::color).collect()
+    }
+
+    pub fn write(&self, path: &str, format: ImageFormat) -> io::Result<()> {
+        format
+            .writer()
+            .write(path, self.width, self.height, &self.to_image())
+    }
+}
+
+// A rectangle of the film with its own pixels, so tiles can be rendered without sharing
+// anything and merged back afterwards.
+pub struct FilmTile {
+    pub x0: usize,
+    pub row0: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Pixel>,
+}
+
+impl FilmTile {
+    pub fn new(x0: usize, row0: usize, width: usize, height: usize) -> Self {
+        Self {
+            x0,
+            row0,
+            width,
+            height,
+            pixels: vec![Pixel::new(); width * height],
+        }
+    }
+
+    // x and row in film coordinates
+    pub fn pixel_mut(&mut self, x: usize, row: usize) -> &mut Pixel {
+        &mut self.pixels[(row - self.row0) * self.width + (x - self.x0)]
+    }
+}
@@ -5,6 +5,9 @@ use gltf::buffer::Buffer;
 use crate::matrix4::Matrix4;
 use crate::vec3::*;
 use crate::material::*;
+use crate::texture::SolidColorTexture;
+use serde::Deserialize;
+use std::fs;
 use std::sync::Arc;
 
 pub struct GLTF {
@@ -42,31 +45,102 @@ pub struct GLTFMaterial {
     pub albedo_alpha: f32,
     pub metallic: f32,
     pub roughness: f32,
+    // KHR_materials_transmission, light going through instead of into a diffuse base
+    pub transmission: f32,
+    // KHR_materials_ior
+    pub ior: f32,
+    // KHR_materials_volume, absorption inside. None for thin walled materials
+    pub absorption: Option<Color>,
 }
 
 impl GLTF {
     pub fn new(fname: String) -> Result<Self, gltf::Error> {
-        let (document, buffers, images) = gltf::import(fname)?;
+        let (document, buffers, images) = gltf::import(&fname)?;
 
         let (nodes, meshes) = process_nodes(&document, &buffers);
-        let materials = process_materials(&document);
+        let materials = process_materials(&document, &material_extensions(&fname)?);
 
         Ok(Self { nodes, meshes, materials })
     }
 }
 
 impl GLTFMaterial {
-    pub fn metallic_roughness(&self) -> (Color, f32) {
-        let red = lerp(self.albedo[0], 1.0, self.metallic);
-        let green = lerp(self.albedo[1], 1.0, self.metallic);
-        let blue = lerp(self.albedo[2], 1.0, self.metallic);
-
-        (Color::new(red, green, blue), self.roughness)
+    // Transmissive materials are glass all the way through, tinted by the base colour: solid
+    // with a volume, a thin sheet without one, as the extensions have it. There is no mix of
+    // glass and the opaque base, so any transmission at all is rounded up to full.
+    pub fn material(&self) -> Arc<dyn Material> {
+        let albedo = SolidColorTexture::new(self.albedo);
+        if self.transmission <= 0.0 {
+            return Arc::new(MetallicRoughness::new(albedo, self.metallic, self.roughness));
+        }
+        match self.absorption {
+            Some(absorption) => {
+                Arc::new(RoughDielectric::new(self.ior, self.roughness, albedo, absorption))
+            }
+            None => Arc::new(ThinDielectric::new(self.ior, albedo)),
+        }
     }
 }
 
-fn lerp(v0: f32, v1: f32, t: f32) -> f32 {
-    v0 + t * (v1 - v0)
+// The material extensions gltf doesn't read, straight from the JSON
+#[derive(Deserialize, Default)]
+struct RawRoot {
+    #[serde(default)]
+    materials: Vec<RawMaterial>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawMaterial {
+    #[serde(default)]
+    extensions: RawExtensions,
+}
+
+#[derive(Deserialize, Default)]
+struct RawExtensions {
+    #[serde(rename = "KHR_materials_transmission")]
+    transmission: Option<Transmission>,
+    #[serde(rename = "KHR_materials_ior")]
+    ior: Option<Ior>,
+    #[serde(rename = "KHR_materials_volume")]
+    volume: Option<Volume>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transmission {
+    #[serde(default)]
+    transmission_factor: f32,
+}
+
+#[derive(Deserialize)]
+struct Ior {
+    #[serde(default = "default_ior")]
+    ior: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Volume {
+    #[serde(default)]
+    thickness_factor: f32,
+    attenuation_distance: Option<f32>,
+    attenuation_color: Option<[f32; 3]>,
+}
+
+fn default_ior() -> f32 {
+    1.5
+}
+
+fn material_extensions(fname: &str) -> Result<Vec<RawMaterial>, gltf::Error> {
+    let data = fs::read(fname).map_err(gltf::Error::Io)?;
+    let root: RawRoot = if data.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(&data)?;
+        gltf::json::deserialize::from_slice(&glb.json)
+    } else {
+        gltf::json::deserialize::from_slice(&data)
+    }
+    .map_err(gltf::Error::Deserialize)?;
+    Ok(root.materials)
 }
 
 fn process_nodes<'a>(
@@ -115,14 +189,22 @@ fn process_nodes<'a>(
     process_node_parents(&mut nodes);
     process_global_transforms(&mut nodes);
 
-    for node in nodes {
-        meshes.extend(process_meshes(document.scenes().into_iter().next().nodes()[node.index], buffers).unwrap());
+    for node in &nodes {
+        if let Some(gltf_node) = document.nodes().nth(node.index) {
+            if let Some(node_meshes) = process_meshes(&gltf_node, node.global_transform, buffers) {
+                meshes.extend(node_meshes);
+            }
+        }
     }
 
     (nodes, meshes)
 }
 
-fn process_meshes(node: &gltf::Node, buffers: &[gltf::buffer::Data]) -> Option<Vec<Mesh>> {
+fn process_meshes(
+    node: &gltf::Node,
+    transform: Matrix4,
+    buffers: &[gltf::buffer::Data],
+) -> Option<Vec<Mesh>> {
     node.mesh().map(|mesh| {
         mesh.primitives()
             .map(|primitive| {
@@ -174,14 +256,14 @@ fn process_meshes(node: &gltf::Node, buffers: &[gltf::buffer::Data]) -> Option<V
                     normals,
                     uvs,
                     mat_index,
-                    transform: node.global_transform,
+                    transform,
                 }
             })
             .collect()
     })
 }
 
-fn process_materials(document: &gltf::Document) -> Vec<GLTFMaterial> {
+fn process_materials(document: &gltf::Document, extensions: &[RawMaterial]) -> Vec<GLTFMaterial> {
     document
     .materials()
     .into_iter()
@@ -230,11 +312,41 @@ fn process_materials(document: &gltf::Document) -> Vec<GLTFMaterial> {
         let metallic = metallic_roughness.metallic_factor();
         let roughness = metallic_roughness.roughness_factor();
 
+        let extensions = mat
+            .index()
+            .and_then(|i| extensions.get(i))
+            .map(|m| &m.extensions);
+        let transmission = extensions
+            .and_then(|e| e.transmission.as_ref())
+            .map_or(0.0, |t| t.transmission_factor);
+        if transmission > 0.0 && transmission < 1.0 {
+            eprintln!(
+                "warning: material '{}' is {}% transmissive, rendering it as fully transmissive",
+                mat.name().unwrap_or("unnamed"),
+                transmission * 100.0
+            );
+        }
+        let ior = extensions
+            .and_then(|e| e.ior.as_ref())
+            .map_or(default_ior(), |i| i.ior);
+        // a thickness of 0 also means thin walled
+        let absorption = extensions
+            .and_then(|e| e.volume.as_ref())
+            .filter(|v| v.thickness_factor > 0.0)
+            .map(|v| {
+                let color = v.attenuation_color.unwrap_or([1.0, 1.0, 1.0]);
+                let color = Color::new(color[0], color[1], color[2]);
+                absorption_at_distance(color, v.attenuation_distance.unwrap_or(f32::INFINITY))
+            });
+
         GLTFMaterial {
             albedo,
             albedo_alpha,
             metallic,
             roughness,
+            transmission,
+            ior,
+            absorption,
         }
     })
     .collect()
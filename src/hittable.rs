@@ -1,14 +1,14 @@
 use crate::aabb::AABB;
 use crate::material::{Isotropic, Material};
 use crate::ray::Ray;
+use crate::rng;
+use crate::sampler::Sampler;
 use crate::texture::Texture;
 use crate::vec3::{Point3, Vec3};
 
 use std::f32;
 use std::sync::Arc;
 
-use rand::prelude::*;
-
 pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
@@ -36,9 +36,50 @@ pub trait Hittable: Sync + Send {
     fn pdf_value(&self, _orig: Point3, _v: Vec3) -> f32 {
         0.0
     }
-    fn random(&self, _orig: Vec3) -> Vec3 {
+    fn random(&self, _orig: Vec3, _sampler: &mut dyn Sampler) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // true for emissive shapes that `pdf_value` and `random` can sample as area lights
+    fn is_area_light(&self) -> bool {
+        false
+    }
+    // collects the area lights among the objects this one is made of
+    fn area_lights(&self, _lights: &mut Vec<Arc<dyn Hittable>>) {}
+    // surface area, for estimating how much light an emissive shape gives off
+    fn area(&self) -> f32 {
+        0.0
+    }
+    // the side a one-sided surface emits towards, None when it lights both sides
+    fn facing(&self) -> Option<Vec3> {
+        None
+    }
+}
+
+impl Hittable for Arc<dyn Hittable> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        (**self).bounding_box(time0, time1)
+    }
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        (**self).pdf_value(orig, v)
+    }
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        (**self).random(orig, sampler)
+    }
+    fn is_area_light(&self) -> bool {
+        (**self).is_area_light()
+    }
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        (**self).area_lights(lights)
+    }
+    fn area(&self) -> f32 {
+        (**self).area()
+    }
+    fn facing(&self) -> Option<Vec3> {
+        (**self).facing()
+    }
 }
 
 #[derive(Clone)]
@@ -113,6 +154,9 @@ impl Hittable for HittableList {
     }
 
     fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
         self.objects
             .iter()
             .map(|h| h.pdf_value(orig, v))
@@ -120,11 +164,24 @@ impl Hittable for HittableList {
             / self.objects.len() as f32
     }
 
-    fn random(&self, orig: Vec3) -> Vec3 {
-        self.objects
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .random(orig)
+    // an empty list has nothing to aim at, callers check `pdf_value` before using the direction
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let n = self.objects.len();
+        if n == 0 {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let i = ((sampler.get_1d() * n as f32) as usize).min(n - 1);
+        self.objects[i].random(orig, sampler)
+    }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        for object in &self.objects {
+            if object.is_area_light() {
+                lights.push(object.clone());
+            } else {
+                object.area_lights(lights);
+            }
+        }
     }
 }
 
@@ -165,7 +222,7 @@ impl Hittable for ConstantMedium {
 
                 let ray_length = r.dir.length();
                 let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-                let hit_distance = self.neg_inv_density * rand::random::<f32>().ln();
+                let hit_distance = self.neg_inv_density * rng::random::<f32>().ln();
 
                 if hit_distance > distance_inside_boundary {
                     return None;
@@ -218,4 +275,24 @@ impl Hittable for FlipFace {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
         self.hit.bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        self.hit.pdf_value(orig, v)
+    }
+
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.hit.random(orig, sampler)
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.hit.is_area_light()
+    }
+
+    fn area(&self) -> f32 {
+        self.hit.area()
+    }
+
+    fn facing(&self) -> Option<Vec3> {
+        self.hit.facing()
+    }
 }
@@ -0,0 +1,719 @@
+use crate::adaptive;
+use crate::bvh;
+use crate::hittable::*;
+use crate::light::{AreaLight, Light, LightSample};
+use crate::light_sampler::{LightSampler, LightSamplerKind};
+use crate::material::*;
+use crate::onb::ONB;
+use crate::pdf::*;
+use crate::ray::Ray;
+use crate::render::{count_ray, Settings};
+use crate::sampler::{self, sample_cosine_hemisphere, Sampler};
+use crate::spectrum;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// What the integrators see of the scene: the geometry, the lights to aim shadow rays at and
+// the radiance of rays that escape.
+pub struct Scene<'a> {
+    pub world: &'a HittableList,
+    // the given lights, plus an area light for every emissive shape found in the world
+    pub lights: Vec<Arc<dyn Light>>,
+    light_sampler: Box<dyn LightSampler>,
+    background: Color,
+}
+
+impl<'a> Scene<'a> {
+    pub fn new(
+        world: &'a HittableList,
+        lights: &[Arc<dyn Light>],
+        background: Color,
+        light_sampler: LightSamplerKind,
+    ) -> Self {
+        let mut shapes = vec![];
+        world.area_lights(&mut shapes);
+        let mut all: Vec<Arc<dyn Light>> = shapes
+            .into_iter()
+            .map(|shape| Arc::new(AreaLight::new(shape)) as Arc<dyn Light>)
+            .collect();
+        all.extend(lights.iter().cloned());
+
+        Self {
+            world,
+            light_sampler: light_sampler.create(&all, 0.5 * extent(world)),
+            lights: all,
+            background,
+        }
+    }
+
+    // closest hit along `ray`, counted in the rays/s statistics
+    pub fn hit(&self, ray: &Ray) -> Option<HitRecord<'a>> {
+        count_ray();
+        self.world.hit(ray, 0.001, f32::INFINITY)
+    }
+
+    // whether `shadow` gets `distance` far without hitting anything
+    pub fn unoccluded(&self, shadow: &Ray, distance: f32) -> bool {
+        count_ray();
+        // stop short of the light, which for area lights is part of the world
+        let t_max = distance * (1.0 - 1e-3) / shadow.dir.length();
+        self.world.hit(shadow, 0.001, t_max).is_none()
+    }
+
+    // A sample of one of the lights, picked by the light sampler. The pdf includes the odds
+    // of picking that light.
+    pub fn sample_light(
+        &self,
+        p: Point3,
+        time: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let (i, pmf) = self.light_sampler.pick(p, sampler.get_1d())?;
+        let mut sample = self.lights[i].sample(p, time, sampler)?;
+        sample.pdf *= pmf;
+        Some(sample)
+    }
+
+    // density of `sample_light` picking direction `dir` from `p`
+    pub fn light_pdf(&self, p: Point3, dir: Vec3) -> f32 {
+        self.light_sampler.pdf(p, dir)
+    }
+
+    // light arriving along rays that hit nothing, from every direction
+    pub fn background(&self) -> Color {
+        spectrum::uplift(self.background)
+    }
+
+    // light from lights at infinity arriving along a ray that leaves the scene, on top of
+    // the background
+    pub fn escaped(&self, dir: Vec3) -> Color {
+        self.lights
+            .iter()
+            .fold(Color::new_empty(), |sum, l| sum + l.escaped(dir))
+    }
+
+    fn extent(&self) -> f32 {
+        extent(self.world)
+    }
+}
+
+// length of the diagonal of the scene bounds, to put distances on a 0..1 scale
+fn extent(world: &HittableList) -> f32 {
+    world
+        .bounding_box(0.0, 1.0)
+        .map(|b| (b.max - b.min).length())
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .unwrap_or(1.0)
+}
+
+// A light transport algorithm: the radiance arriving at the camera along a ray.
+pub trait Integrator: Sync + Send {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegratorKind {
+    Path,
+    Whitted,
+    AmbientOcclusion,
+    Direct,
+    Normals,
+    Depth,
+    Uv,
+    BvhCost,
+    MaterialId,
+}
+
+impl IntegratorKind {
+    pub const NAMES: &'static str = "path, whitted, ao, direct, normals, depth, uv, bvh-cost, \
+                                     material-id";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "path" => Some(IntegratorKind::Path),
+            "whitted" => Some(IntegratorKind::Whitted),
+            "ao" => Some(IntegratorKind::AmbientOcclusion),
+            "direct" => Some(IntegratorKind::Direct),
+            "normals" => Some(IntegratorKind::Normals),
+            "depth" => Some(IntegratorKind::Depth),
+            "uv" => Some(IntegratorKind::Uv),
+            "bvh-cost" => Some(IntegratorKind::BvhCost),
+            "material-id" => Some(IntegratorKind::MaterialId),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegratorKind::Path => "path",
+            IntegratorKind::Whitted => "whitted",
+            IntegratorKind::AmbientOcclusion => "ao",
+            IntegratorKind::Direct => "direct",
+            IntegratorKind::Normals => "normals",
+            IntegratorKind::Depth => "depth",
+            IntegratorKind::Uv => "uv",
+            IntegratorKind::BvhCost => "bvh-cost",
+            IntegratorKind::MaterialId => "material-id",
+        }
+    }
+
+    // whether it renders light, which spectral mode can trace by wavelength, rather than a
+    // property of the surfaces
+    pub fn traces_light(&self) -> bool {
+        matches!(
+            self,
+            IntegratorKind::Path | IntegratorKind::Whitted | IntegratorKind::Direct
+        )
+    }
+
+    pub fn create(&self, settings: &Settings, scene: &Scene) -> Box<dyn Integrator> {
+        let max_depth = settings.max_depth;
+        match self {
+            IntegratorKind::Path => Box::new(PathIntegrator {
+                max_depth,
+                max_diffuse: settings.max_diffuse,
+                max_specular: settings.max_specular,
+                max_volume: settings.max_volume,
+                rr_depth: settings.rr_depth,
+            }),
+            IntegratorKind::Whitted => Box::new(WhittedIntegrator { max_depth }),
+            IntegratorKind::AmbientOcclusion => Box::new(AmbientOcclusionIntegrator {
+                distance: AO_DISTANCE * scene.extent(),
+            }),
+            IntegratorKind::Direct => Box::new(DirectLightingIntegrator { max_depth }),
+            IntegratorKind::Normals => Box::new(NormalsIntegrator),
+            IntegratorKind::Depth => Box::new(DepthIntegrator {
+                extent: scene.extent(),
+            }),
+            IntegratorKind::Uv => Box::new(UvIntegrator),
+            IntegratorKind::BvhCost => Box::new(BvhCostIntegrator),
+            IntegratorKind::MaterialId => Box::new(MaterialIdIntegrator),
+        }
+    }
+}
+
+// Unidirectional path tracing with next event estimation: every diffuse vertex samples the
+// lights through a shadow ray and picks the next direction from the material's distribution,
+// and the two are combined with multiple importance sampling. Paths end after `max_depth`
+// bounces, or earlier once they run out of bounces of one kind, and past `rr_depth` bounces
+// Russian roulette ends the ones that carry little light left. Paths keep track of the glass
+// they're inside of, whose absorption dims them by the distance they travel through it.
+pub struct PathIntegrator {
+    pub max_depth: i32,
+    pub max_diffuse: i32,
+    pub max_specular: i32,
+    pub max_volume: i32,
+    pub rr_depth: i32,
+}
+
+// bounces of each kind a path has taken so far
+#[derive(Debug, Clone, Copy, Default)]
+struct Bounces {
+    diffuse: i32,
+    specular: i32,
+    volume: i32,
+}
+
+impl Bounces {
+    fn total(&self) -> i32 {
+        self.diffuse + self.specular + self.volume
+    }
+}
+
+// Everything a path carries from one bounce to the next.
+struct PathState {
+    // the ray leaving the last vertex
+    ray: Ray,
+    // weight the camera gives to light arriving along `ray`
+    throughput: Color,
+    // light gathered so far
+    radiance: Color,
+    bounces: Bounces,
+    // density the material sampled `ray` with when it left a diffuse vertex that also sampled
+    // the lights, so an emitter it hits is weighted against that light sample
+    bsdf_pdf: Option<f32>,
+    // absorption of the medium `ray` travels through, black outside of everything
+    medium: Color,
+}
+
+impl PathState {
+    fn new(ray: Ray) -> Self {
+        Self {
+            ray,
+            throughput: Color::new(1.0, 1.0, 1.0),
+            radiance: Color::new_empty(),
+            bounces: Bounces::default(),
+            bsdf_pdf: None,
+            medium: Color::new_empty(),
+        }
+    }
+
+    // light arriving along the current ray
+    fn add(&mut self, light: Color) {
+        self.radiance += self.throughput * light;
+    }
+
+    // light arriving along the current ray from something the lights could have sampled too
+    fn add_emitted(&mut self, light: Color, scene: &Scene) {
+        match self.bsdf_pdf {
+            Some(pdf) if !light.near_zero() => {
+                let light_pdf = scene.light_pdf(self.ray.orig, self.ray.dir);
+                self.add(power_heuristic(pdf, light_pdf) * light);
+            }
+            _ => self.add(light),
+        }
+    }
+
+    // carries on along `ray`, whose light is worth `weight` times the current ray's
+    fn bounce(&mut self, ray: Ray, weight: Color, bsdf_pdf: Option<f32>) {
+        self.ray = ray;
+        self.throughput *= weight;
+        self.bsdf_pdf = bsdf_pdf;
+    }
+}
+
+// Absorption of the medium a ray leaving `hit` along `dir` travels through, for a ray that was
+// in `medium` until then. Surfaces that let light in take rays going into them into the
+// material's medium and rays leaving them out into the open, the others leave it as it was.
+fn medium_after(hit: &HitRecord, dir: Vec3, medium: Color) -> Color {
+    match hit.material.absorption() {
+        Some(absorption) => {
+            let outward = if hit.front_face {
+                hit.normal
+            } else {
+                -hit.normal
+            };
+            if dir.dot(outward) < 0.0 {
+                absorption
+            } else {
+                Color::new_empty()
+            }
+        }
+        None => medium,
+    }
+}
+
+// fraction of the light left after `distance` through a medium with `absorption`
+fn transmittance(absorption: Color, distance: f32) -> Color {
+    let channel = |a: f32| if a > 0.0 { (-a * distance).exp() } else { 1.0 };
+    Color::new(
+        channel(absorption.x),
+        channel(absorption.y),
+        channel(absorption.z),
+    )
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut path = PathState::new(ray);
+
+        loop {
+            let hit = scene.hit(&path.ray);
+            // Beer-Lambert: the farther through a medium, the less light is left
+            if !path.medium.near_zero() {
+                let distance = hit.as_ref().map_or(f32::INFINITY, |h| h.t) * path.ray.dir.length();
+                path.throughput *= transmittance(path.medium, distance);
+            }
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    let escaped = scene.escaped(path.ray.dir);
+                    path.add(scene.background());
+                    path.add_emitted(escaped, scene);
+                    break;
+                }
+            };
+            path.add_emitted(hit.material.emitted(&path.ray, &hit), scene);
+
+            let depth = path.bounces.total();
+            if depth >= self.max_depth {
+                break;
+            }
+
+            // a path that goes on with probability p has its light divided by p, so on
+            // average nothing is lost
+            if depth >= self.rr_depth {
+                let t = path.throughput;
+                let p = t.x.max(t.y).max(t.z).clamp(0.05, 1.0);
+                if sampler.get_1d() >= p {
+                    break;
+                }
+                path.throughput /= p;
+            }
+
+            match hit.material.scatter(&path.ray, &hit, sampler) {
+                Some(ReflectionRecord::Specular {
+                    specular_ray,
+                    attenuation,
+                }) => {
+                    if path.bounces.specular >= self.max_specular {
+                        break;
+                    }
+                    path.bounces.specular += 1;
+                    path.medium = medium_after(&hit, specular_ray.dir, path.medium);
+                    path.bounce(specular_ray, attenuation, None);
+                }
+
+                Some(ReflectionRecord::Volume {
+                    scattered_ray,
+                    attenuation,
+                }) => {
+                    if path.bounces.volume >= self.max_volume {
+                        break;
+                    }
+                    path.bounces.volume += 1;
+                    path.bounce(scattered_ray, attenuation, None);
+                }
+
+                Some(ReflectionRecord::Scatter {
+                    pdf: reflection_pdf,
+                    attenuation,
+                }) => {
+                    if path.bounces.diffuse >= self.max_diffuse {
+                        break;
+                    }
+
+                    // next event estimation: light straight from the lights, through a
+                    // shadow ray, and the light the material's own sample finds further on,
+                    // each weighted by how well its strategy fits the direction
+                    let lit = !scene.lights.is_empty();
+                    let direct = sample_lights(
+                        &path.ray,
+                        &hit,
+                        attenuation,
+                        Some(&*reflection_pdf),
+                        path.medium,
+                        scene,
+                        sampler,
+                    );
+                    path.add(direct);
+
+                    let dir = reflection_pdf.generate(sampler);
+                    let pdf_val = reflection_pdf.value(dir);
+                    if pdf_val <= 0.0 {
+                        break;
+                    }
+                    let scattered = Ray::new(hit.p, dir, path.ray.time);
+                    let weight = hit
+                        .material
+                        .scattering(&path.ray, &hit, &scattered, attenuation)
+                        / pdf_val;
+
+                    path.bounces.diffuse += 1;
+                    path.medium = medium_after(&hit, dir, path.medium);
+                    path.bounce(scattered, weight, Some(pdf_val).filter(|_| lit));
+                }
+
+                None => break,
+            }
+        }
+
+        path.radiance
+    }
+}
+
+// Classic recursive ray tracing, for quick previews: mirrors and glass are followed, diffuse
+// surfaces get one shadow ray towards the lights plus the background as a flat ambient term,
+// and there is no indirect diffuse light.
+pub struct WhittedIntegrator {
+    pub max_depth: i32,
+}
+
+impl WhittedIntegrator {
+    // `medium` is the absorption along `ray`, as in the path integrator
+    fn trace(
+        &self,
+        ray: Ray,
+        medium: Color,
+        scene: &Scene,
+        depth: i32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if depth <= 0 {
+            return Color::new_empty();
+        }
+
+        let hit = match scene.hit(&ray) {
+            Some(hit) => hit,
+            None => {
+                return transmittance(medium, f32::INFINITY)
+                    * (scene.background() + scene.escaped(ray.dir))
+            }
+        };
+        let absorbed = transmittance(medium, hit.t * ray.dir.length());
+        let emitted = hit.material.emitted(&ray, &hit);
+
+        let radiance = match hit.material.scatter(&ray, &hit, sampler) {
+            Some(ReflectionRecord::Specular {
+                specular_ray: next,
+                attenuation,
+            })
+            | Some(ReflectionRecord::Volume {
+                scattered_ray: next,
+                attenuation,
+            }) => {
+                let medium = medium_after(&hit, next.dir, medium);
+                attenuation * self.trace(next, medium, scene, depth - 1, sampler)
+            }
+
+            Some(ReflectionRecord::Scatter { attenuation, .. }) => {
+                emitted
+                    + sample_lights(&ray, &hit, attenuation, None, medium, scene, sampler)
+                    + attenuation * scene.background()
+            }
+
+            None => emitted,
+        };
+        absorbed * radiance
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.trace(ray, Color::new_empty(), scene, self.max_depth, sampler)
+    }
+}
+
+// Light that reaches the first diffuse surface straight from an emitter: one shadow ray towards
+// the lights, and one ray from the material's distribution that only counts if it escapes to
+// the background. Mirrors and glass in front of that surface are followed.
+pub struct DirectLightingIntegrator {
+    pub max_depth: i32,
+}
+
+impl DirectLightingIntegrator {
+    // `medium` is the absorption along `ray`, as in the path integrator
+    fn trace(
+        &self,
+        ray: Ray,
+        medium: Color,
+        scene: &Scene,
+        depth: i32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if depth <= 0 {
+            return Color::new_empty();
+        }
+
+        let hit = match scene.hit(&ray) {
+            Some(hit) => hit,
+            None => {
+                return transmittance(medium, f32::INFINITY)
+                    * (scene.background() + scene.escaped(ray.dir))
+            }
+        };
+        let absorbed = transmittance(medium, hit.t * ray.dir.length());
+        let emitted = hit.material.emitted(&ray, &hit);
+
+        let radiance = match hit.material.scatter(&ray, &hit, sampler) {
+            Some(ReflectionRecord::Specular {
+                specular_ray: next,
+                attenuation,
+            })
+            | Some(ReflectionRecord::Volume {
+                scattered_ray: next,
+                attenuation,
+            }) => {
+                let medium = medium_after(&hit, next.dir, medium);
+                attenuation * self.trace(next, medium, scene, depth - 1, sampler)
+            }
+
+            Some(ReflectionRecord::Scatter { pdf, attenuation }) => {
+                let dir = pdf.generate(sampler);
+                let scattered = Ray::new(hit.p, dir, ray.time);
+                let pdf_val = pdf.value(dir);
+                // emitters are already covered by the shadow ray
+                let sky = if pdf_val > 0.0 && scene.hit(&scattered).is_none() {
+                    let left = transmittance(medium_after(&hit, dir, medium), f32::INFINITY);
+                    hit.material.scattering(&ray, &hit, &scattered, attenuation)
+                        * left
+                        * scene.background()
+                        / pdf_val
+                } else {
+                    Color::new_empty()
+                };
+
+                emitted + sample_lights(&ray, &hit, attenuation, None, medium, scene, sampler) + sky
+            }
+
+            None => emitted,
+        };
+        absorbed * radiance
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.trace(ray, Color::new_empty(), scene, self.max_depth, sampler)
+    }
+}
+
+// One sample of the light arriving at `hit` straight from the scene's lights, scattered
+// towards `ray`'s origin, which travelled through `medium`. With the material's distribution
+// as `bsdf_pdf`, the sample is weighted against that distribution having picked the same
+// direction.
+fn sample_lights(
+    ray: &Ray,
+    hit: &HitRecord,
+    attenuation: Color,
+    bsdf_pdf: Option<&dyn PDF>,
+    medium: Color,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let light = match scene.sample_light(hit.p, ray.time, sampler) {
+        Some(light) if light.pdf > 0.0 && !light.radiance.near_zero() => light,
+        _ => return Color::new_empty(),
+    };
+
+    let shadow = Ray::new(hit.p, light.dir, ray.time);
+    let scattering = hit.material.scattering(ray, hit, &shadow, attenuation);
+    if scattering.near_zero() || !scene.unoccluded(&shadow, light.distance) {
+        return Color::new_empty();
+    }
+
+    let weight = match bsdf_pdf {
+        Some(bsdf_pdf) if !light.delta => power_heuristic(light.pdf, bsdf_pdf.value(light.dir)),
+        _ => 1.0,
+    };
+    let absorbed = transmittance(medium_after(hit, light.dir, medium), light.distance);
+    weight * scattering * absorbed * light.radiance / light.pdf
+}
+
+// Multiple importance sampling weight of a sample drawn with density `pdf`, when another
+// strategy could have drawn it with density `other`. Squaring favours whichever strategy is
+// the better fit more strongly than the plain ratio does. An infinite density, from a light too
+// small or far to resolve, takes the whole weight rather than dividing infinity by itself.
+fn power_heuristic(pdf: f32, other: f32) -> f32 {
+    if pdf.is_infinite() {
+        return 1.0;
+    }
+    if other.is_infinite() {
+        return 0.0;
+    }
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+// fraction of the scene size an ambient occlusion ray looks for occluders in
+const AO_DISTANCE: f32 = 0.1;
+
+// White where the hemisphere above the first hit is open, darker the more of it is blocked
+// within `distance`. Rays that miss everything are black.
+pub struct AmbientOcclusionIntegrator {
+    pub distance: f32,
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let hit = match scene.hit(&ray) {
+            Some(hit) => hit,
+            None => return Color::new_empty(),
+        };
+
+        let uvw = ONB::build_from_w(hit.normal);
+        let dir = uvw.local_vec3(sample_cosine_hemisphere(sampler.get_2d()));
+
+        count_ray();
+        let probe = Ray::new(hit.p, dir, ray.time);
+        let distance = self.distance / dir.length();
+        match scene.world.hit(&probe, 0.001, distance) {
+            Some(_) => Color::new_empty(),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// The debug integrators below show one property of the first hit as a colour meant to be
+// looked at in an 8 bit image. The writers gamma correct, so colours are squared on the way out
+// to come out as is.
+fn display(c: Color) -> Color {
+    c * c
+}
+
+// outward facing normal of the first hit, mapped from -1..1 to 0..1
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Color {
+        match scene.hit(&ray) {
+            Some(hit) => {
+                let n = if hit.front_face {
+                    hit.normal
+                } else {
+                    -hit.normal
+                };
+                display(0.5 * (n.unit_vector() + Vec3::new(1.0, 1.0, 1.0)))
+            }
+            None => Color::new_empty(),
+        }
+    }
+}
+
+// distance to the first hit as a fraction of the scene size, black up close to white far away
+pub struct DepthIntegrator {
+    pub extent: f32,
+}
+
+impl Integrator for DepthIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Color {
+        let depth = match scene.hit(&ray) {
+            Some(hit) => (hit.t * ray.dir.length() / self.extent).min(1.0),
+            None => 1.0,
+        };
+        display(Color::new(depth, depth, depth))
+    }
+}
+
+// texture coordinates of the first hit, u in red and v in green
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Color {
+        match scene.hit(&ray) {
+            Some(hit) => display(Color::new(hit.u, hit.v, 0.0)),
+            None => Color::new_empty(),
+        }
+    }
+}
+
+// BVH nodes visited by the camera ray at which the cost ramp tops out
+const BVH_COST_MAX: f32 = 100.0;
+
+// Number of BVH nodes the camera ray visits, as a blue (few) to red (many) ramp. Objects that
+// aren't in a BVH cost nothing.
+pub struct BvhCostIntegrator;
+
+impl Integrator for BvhCostIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Color {
+        bvh::take_node_count();
+        scene.hit(&ray);
+        let cost = bvh::take_node_count() as f32;
+        adaptive::ramp((cost / BVH_COST_MAX).min(1.0))
+    }
+}
+
+// a colour per material of the first hit
+pub struct MaterialIdIntegrator;
+
+impl Integrator for MaterialIdIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Color {
+        match scene.hit(&ray) {
+            Some(hit) => {
+                let id = hit.material.id() as u64;
+                let h = sampler::hash((id ^ (id >> 32)) as u32);
+                let channel = |shift: u32| ((h >> shift) & 0xff) as f32 / 255.0;
+                display(Color::new(channel(0), channel(8), channel(16)))
+            }
+            None => Color::new_empty(),
+        }
+    }
+}
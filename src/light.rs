@@ -0,0 +1,468 @@
+use crate::aabb::AABB;
+use crate::hittable::Hittable;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::spectrum;
+use crate::vec3::*;
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// lights a scene adds on top of the emissive shapes in its world
+pub type LightList = Vec<Arc<dyn Light>>;
+
+// Light arriving at a point from one sampled point or direction of a light.
+pub struct LightSample {
+    // unit direction from the lit point towards the light
+    pub dir: Vec3,
+    // how far the light is along `dir`, infinite for lights at infinity
+    pub distance: f32,
+    // light arriving along `dir`
+    pub radiance: Color,
+    // solid angle density of `dir`. Delta lights can only be sampled one way and use 1
+    pub pdf: f32,
+    pub delta: bool,
+}
+
+// Something that emits light and can be sampled from a point being lit, so the integrators
+// can aim shadow rays at it.
+pub trait Light: Sync + Send {
+    fn sample(&self, p: Point3, time: f32, sampler: &mut dyn Sampler) -> Option<LightSample>;
+
+    // solid angle density of `sample` picking `dir` from `p`; 0 for delta lights, which no
+    // other sampling strategy can hit
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32;
+
+    // light arriving along a ray that leaves the scene in direction `dir`
+    fn escaped(&self, _dir: Vec3) -> Color {
+        Color::new_empty()
+    }
+
+    // Total light given off, as luminance, so brighter lights can be picked more often. Lights
+    // at infinity count what falls on a disk as wide as the scene, `scene_radius`.
+    fn power(&self, scene_radius: f32) -> f32;
+
+    // where the light is and which way it shines, for the light tree. None for lights at
+    // infinity
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+// Bounds of one light or a group of them: the box they sit in, their power and the cone of
+// directions they shine in. Every point shines within `theta_o` of `w`, and light leaves it
+// up to `theta_e` further out.
+#[derive(Clone)]
+pub struct LightBounds {
+    pub bbox: AABB,
+    pub phi: f32,
+    pub w: Vec3,
+    pub cos_theta_o: f32,
+    pub cos_theta_e: f32,
+}
+
+impl LightBounds {
+    // lights shining equally in every direction
+    pub fn omni(bbox: AABB, phi: f32) -> Self {
+        Self {
+            bbox,
+            phi,
+            w: Vec3::new(0.0, 0.0, 1.0),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        if self.phi <= 0.0 {
+            return other.clone();
+        }
+        if other.phi <= 0.0 {
+            return self.clone();
+        }
+
+        let (w, theta_o) = union_cones(
+            (self.w, self.cos_theta_o.acos()),
+            (other.w, other.cos_theta_o.acos()),
+        );
+        Self {
+            bbox: AABB::surrounding_box(&self.bbox, &other.bbox),
+            phi: self.phi + other.phi,
+            w,
+            cos_theta_o: theta_o.cos(),
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+        }
+    }
+
+    // An estimate of how much of the light reaches `p`, that is never 0 for lights that can
+    // reach it: the power over the squared distance, and the cosine of the smallest angle
+    // between the cone and the direction to `p` the box allows.
+    pub fn importance(&self, p: Point3) -> f32 {
+        if self.phi <= 0.0 {
+            return 0.0;
+        }
+
+        let center = 0.5 * (self.bbox.min + self.bbox.max);
+        let radius_squared = 0.25 * (self.bbox.max - self.bbox.min).length_squared();
+        let to_p = p - center;
+        let distance_squared = to_p.length_squared();
+
+        // inside the bounding sphere any direction is possible
+        let theta_b = if distance_squared <= radius_squared {
+            PI
+        } else {
+            (radius_squared / distance_squared).sqrt().asin()
+        };
+        let cos_theta_w = if distance_squared > 0.0 {
+            self.w.dot(to_p) / distance_squared.sqrt()
+        } else {
+            1.0
+        };
+        let theta_w = cos_theta_w.clamp(-1.0, 1.0).acos();
+        let theta = (theta_w - self.cos_theta_o.acos() - theta_b).max(0.0);
+        if theta >= self.cos_theta_e.acos() {
+            return 0.0;
+        }
+
+        self.phi * theta.cos() / distance_squared.max(radius_squared).max(1e-8)
+    }
+}
+
+// smallest cone, as (axis, half angle), holding two others
+fn union_cones(a: (Vec3, f32), b: (Vec3, f32)) -> (Vec3, f32) {
+    let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+    if (theta_d + b.1).min(PI) <= a.1 {
+        return a;
+    }
+    if (theta_d + a.1).min(PI) <= b.1 {
+        return b;
+    }
+
+    let theta_o = 0.5 * (a.1 + theta_d + b.1);
+    let axis = a.0.cross(b.0);
+    if theta_o >= PI || axis.length_squared() == 0.0 {
+        return (a.0, PI);
+    }
+
+    // turn a's axis towards b's, in the plane of both
+    let theta_r = theta_o - a.1;
+    let k = axis.unit_vector();
+    let w = a.0 * theta_r.cos() + k.cross(a.0) * theta_r.sin();
+    (w, theta_o)
+}
+
+// Emissive geometry: the shape's own `random` and `pdf_value` pick points on it, and its
+// material gives the light leaving them. The shape is part of the world too, so rays that
+// hit it see the same light.
+pub struct AreaLight {
+    shape: Arc<dyn Hittable>,
+    power: f32,
+}
+
+impl AreaLight {
+    pub fn new(shape: Arc<dyn Hittable>) -> Self {
+        let power = PI * shape.area() * mean_emission(&*shape);
+        Self { shape, power }
+    }
+}
+
+// shots at a shape from each side of its bounding box
+const EMISSION_PROBES: usize = 4;
+
+// Average luminance of the light leaving a shape, seen from around it. Materials only know
+// what they emit at a hit, so this looks at the shape the way the integrators do.
+fn mean_emission(shape: &dyn Hittable) -> f32 {
+    let bbox = match shape.bounding_box(0.0, 1.0) {
+        Some(bbox) => bbox,
+        None => return 0.0,
+    };
+    let center = 0.5 * (bbox.min + bbox.max);
+    let reach = (bbox.max - bbox.min).length().max(1e-3);
+
+    let mut sampler = SamplerKind::Random.create(1, 0);
+    sampler.start_sample(0, 0, 0);
+    let (mut sum, mut count) = (0.0, 0);
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            let mut p = center;
+            p[axis] += side * reach;
+            for _ in 0..EMISSION_PROBES {
+                let ray = Ray::new(p, shape.random(p, &mut *sampler), 0.0);
+                if let Some(hit) = shape.hit(&ray, 0.001, f32::INFINITY) {
+                    let emitted = hit.material.emitted(&ray, &hit).luminance();
+                    if emitted > 0.0 {
+                        sum += emitted;
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f32
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, p: Point3, time: f32, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.shape.random(p, sampler);
+        let ray = Ray::new(p, to_light, time);
+        let hit = self.shape.hit(&ray, 0.001, f32::INFINITY)?;
+        let pdf = self.shape.pdf_value(p, to_light);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let length = to_light.length();
+        Some(LightSample {
+            dir: to_light / length,
+            distance: hit.t * length,
+            radiance: hit.material.emitted(&ray, &hit),
+            pdf,
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32 {
+        self.shape.pdf_value(p, dir)
+    }
+
+    fn power(&self, _scene_radius: f32) -> f32 {
+        self.power
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        // pad flat shapes, so rays can still hit the box
+        let bbox = self.shape.bounding_box(0.0, 1.0)?;
+        let pad = Vec3::new(1e-4, 1e-4, 1e-4);
+        let bbox = AABB::new(bbox.min - pad, bbox.max + pad);
+        Some(match self.shape.facing() {
+            Some(normal) => LightBounds {
+                bbox,
+                phi: self.power,
+                w: normal,
+                cos_theta_o: 1.0,
+                cos_theta_e: 0.0,
+            },
+            None => LightBounds::omni(bbox, self.power),
+        })
+    }
+}
+
+// Light from a single point, equally in every direction. `intensity` is the radiance a unit
+// distance away, falling off with the squared distance.
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Point3, _time: f32, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            dir: to_light / distance,
+            distance,
+            radiance: spectrum::uplift(self.intensity) / distance_squared,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn pdf(&self, _p: Point3, _dir: Vec3) -> f32 {
+        0.0
+    }
+
+    fn power(&self, _scene_radius: f32) -> f32 {
+        4.0 * PI * self.intensity.luminance()
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let bbox = AABB::new(self.position, self.position);
+        Some(LightBounds::omni(bbox, self.power(0.0)))
+    }
+}
+
+// A point light shining in a cone around `direction`. Full intensity within `falloff_start`
+// degrees of the axis, fading smoothly to nothing at `cone_angle` degrees.
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3,
+    pub intensity: Color,
+    cos_cone: f32,
+    cos_falloff_start: f32,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        cone_angle: f32,
+        falloff_start: f32,
+    ) -> Self {
+        let cone_angle = cone_angle.clamp(0.0, 180.0);
+        Self {
+            position,
+            direction: direction.unit_vector(),
+            intensity,
+            cos_cone: cone_angle.to_radians().cos(),
+            cos_falloff_start: falloff_start.clamp(0.0, cone_angle).to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta >= self.cos_falloff_start {
+            1.0
+        } else if cos_theta <= self.cos_cone {
+            0.0
+        } else {
+            let t = (cos_theta - self.cos_cone) / (self.cos_falloff_start - self.cos_cone);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Point3, _time: f32, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        let distance = distance_squared.sqrt();
+        let dir = to_light / distance;
+
+        let falloff = self.falloff(-dir.dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            dir,
+            distance,
+            radiance: falloff * spectrum::uplift(self.intensity) / distance_squared,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn pdf(&self, _p: Point3, _dir: Vec3) -> f32 {
+        0.0
+    }
+
+    // the falloff counted as half strength
+    fn power(&self, _scene_radius: f32) -> f32 {
+        let solid_angle = 2.0 * PI * (1.0 - 0.5 * (self.cos_falloff_start + self.cos_cone));
+        solid_angle * self.intensity.luminance()
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let theta_e = self.cos_cone.acos() - self.cos_falloff_start.acos();
+        Some(LightBounds {
+            bbox: AABB::new(self.position, self.position),
+            phi: self.power(0.0),
+            w: self.direction,
+            cos_theta_o: self.cos_falloff_start,
+            cos_theta_e: theta_e.cos(),
+        })
+    }
+}
+
+// Light from very far away, like the sun, travelling along `direction`. `irradiance` is the
+// light falling on a surface facing it. With an angular diameter (in degrees, about 0.53 for
+// the sun) the light comes from a small disk of the sky and casts soft shadows; without one
+// it's a single direction and shadows are hard.
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub irradiance: Color,
+    radiance: Color,
+    cos_max: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color, angular_diameter: f32) -> Self {
+        let cos_max = (0.5 * angular_diameter.clamp(0.0, 180.0))
+            .to_radians()
+            .cos();
+        let solid_angle = 2.0 * PI * (1.0 - cos_max);
+        let radiance = if solid_angle > 0.0 {
+            irradiance / solid_angle
+        } else {
+            irradiance
+        };
+
+        Self {
+            direction: direction.unit_vector(),
+            irradiance,
+            radiance,
+            cos_max,
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        self.cos_max >= 1.0
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: Point3, _time: f32, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = -self.direction;
+        if self.is_delta() {
+            return Some(LightSample {
+                dir: to_light,
+                distance: f32::INFINITY,
+                radiance: spectrum::uplift(self.radiance),
+                pdf: 1.0,
+                delta: true,
+            });
+        }
+
+        // uniform over the cone the sun's disk covers
+        let (u, v) = sampler.get_2d();
+        let cos_theta = 1.0 - u * (1.0 - self.cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let uvw = ONB::build_from_w(to_light);
+        let dir = uvw.local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+        Some(LightSample {
+            dir,
+            distance: f32::INFINITY,
+            radiance: spectrum::uplift(self.radiance),
+            pdf: 1.0 / (2.0 * PI * (1.0 - self.cos_max)),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _p: Point3, dir: Vec3) -> f32 {
+        if !self.is_delta() && -dir.unit_vector().dot(self.direction) >= self.cos_max {
+            1.0 / (2.0 * PI * (1.0 - self.cos_max))
+        } else {
+            0.0
+        }
+    }
+
+    fn escaped(&self, dir: Vec3) -> Color {
+        if !self.is_delta() && -dir.unit_vector().dot(self.direction) >= self.cos_max {
+            spectrum::uplift(self.radiance)
+        } else {
+            Color::new_empty()
+        }
+    }
+
+    fn power(&self, scene_radius: f32) -> f32 {
+        PI * scene_radius * scene_radius * self.irradiance.luminance()
+    }
+}
@@ -0,0 +1,439 @@
+use crate::light::{Light, LightBounds};
+use crate::ray::Ray;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// Picks the light a shadow ray is aimed at. `pick` turns a uniform value into the index of a
+// light and the odds of picking it, which may depend on the point being lit.
+pub trait LightSampler: Sync + Send {
+    fn pick(&self, p: Point3, u: f32) -> Option<(usize, f32)>;
+
+    // density of picking a light from `p` and that light sampling `dir`, over all lights
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSamplerKind {
+    Uniform,
+    Power,
+    Tree,
+}
+
+impl LightSamplerKind {
+    pub const NAMES: &'static str = "uniform, power, tree";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "uniform" => Some(LightSamplerKind::Uniform),
+            "power" => Some(LightSamplerKind::Power),
+            "tree" | "bvh" => Some(LightSamplerKind::Tree),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LightSamplerKind::Uniform => "uniform",
+            LightSamplerKind::Power => "power",
+            LightSamplerKind::Tree => "tree",
+        }
+    }
+
+    // `scene_radius` sizes the power of lights at infinity
+    pub fn create(&self, lights: &[Arc<dyn Light>], scene_radius: f32) -> Box<dyn LightSampler> {
+        let lights = lights.to_vec();
+        match self {
+            LightSamplerKind::Uniform => Box::new(UniformLightSampler { lights }),
+            LightSamplerKind::Power => Box::new(PowerLightSampler::new(lights, scene_radius)),
+            LightSamplerKind::Tree => Box::new(LightTree::new(lights, scene_radius)),
+        }
+    }
+}
+
+// n equally likely choices for a uniform value
+fn pick_uniform(u: f32, n: usize) -> usize {
+    ((u * n as f32) as usize).min(n - 1)
+}
+
+// ---------------------------------------------------------------
+
+pub struct UniformLightSampler {
+    lights: Vec<Arc<dyn Light>>,
+}
+
+impl LightSampler for UniformLightSampler {
+    fn pick(&self, _p: Point3, u: f32) -> Option<(usize, f32)> {
+        let n = self.lights.len();
+        if n == 0 {
+            return None;
+        }
+        Some((pick_uniform(u, n), 1.0 / n as f32))
+    }
+
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        self.lights.iter().map(|l| l.pdf(p, dir)).sum::<f32>() / self.lights.len() as f32
+    }
+}
+
+// ---------------------------------------------------------------
+
+// Lights in proportion to their power, the same everywhere in the scene. Falls back to
+// uniform when no light has any power to go by.
+pub struct PowerLightSampler {
+    lights: Vec<Arc<dyn Light>>,
+    pmf: Vec<f32>,
+    cdf: Vec<f32>,
+}
+
+impl PowerLightSampler {
+    fn new(lights: Vec<Arc<dyn Light>>, scene_radius: f32) -> Self {
+        let mut pmf: Vec<f32> = lights
+            .iter()
+            .map(|l| l.power(scene_radius))
+            .map(|power| {
+                if power.is_finite() {
+                    power.max(0.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let total: f32 = pmf.iter().sum();
+        for p in &mut pmf {
+            *p = if total > 0.0 {
+                *p / total
+            } else {
+                1.0 / lights.len() as f32
+            };
+        }
+
+        let cdf = pmf
+            .iter()
+            .scan(0.0, |sum, p| {
+                *sum += p;
+                Some(*sum)
+            })
+            .collect();
+        Self { lights, pmf, cdf }
+    }
+}
+
+impl LightSampler for PowerLightSampler {
+    fn pick(&self, _p: Point3, u: f32) -> Option<(usize, f32)> {
+        let n = self.lights.len();
+        if n == 0 {
+            return None;
+        }
+        // first light whose share of the cdf holds u, which skips lights with none
+        let i = self.cdf.partition_point(|&c| c <= u).min(n - 1);
+        Some((i, self.pmf[i]))
+    }
+
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32 {
+        self.lights
+            .iter()
+            .zip(&self.pmf)
+            .filter(|(_, &pmf)| pmf > 0.0)
+            .map(|(l, pmf)| pmf * l.pdf(p, dir))
+            .sum()
+    }
+}
+
+// ---------------------------------------------------------------
+
+// A bounding volume hierarchy over the lights with a position, walked from the root towards
+// the lights that can contribute most to the point being lit, going by the `LightBounds` of
+// each subtree. Lights at infinity aren't in the tree; they and the tree are picked between
+// uniformly.
+//
+// Scenes with hundreds of small emitters, e.g. the triangles of an emissive mesh, spend their
+// shadow rays on the ones that are close and facing the point instead of spreading them evenly.
+pub struct LightTree {
+    lights: Vec<Arc<dyn Light>>,
+    infinite: Vec<usize>,
+    nodes: Vec<LightNode>,
+}
+
+struct LightNode {
+    bounds: LightBounds,
+    content: NodeContent,
+}
+
+enum NodeContent {
+    Leaf(usize),
+    // the first child follows its parent, this is the index of the second
+    Interior(usize),
+}
+
+impl LightTree {
+    fn new(lights: Vec<Arc<dyn Light>>, scene_radius: f32) -> Self {
+        let mut infinite = vec![];
+        let mut bounded = vec![];
+        for (i, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(bounds) => {
+                    if bounds.phi > 0.0 {
+                        bounded.push((i, bounds));
+                    }
+                }
+                None => {
+                    if light.power(scene_radius) > 0.0 {
+                        infinite.push(i);
+                    }
+                }
+            }
+        }
+
+        let mut tree = Self {
+            lights,
+            infinite,
+            nodes: vec![],
+        };
+        if !bounded.is_empty() {
+            tree.build(&mut bounded);
+        }
+        tree
+    }
+
+    // splits at the middle light along the axis the light centers spread most on
+    fn build(&mut self, lights: &mut [(usize, LightBounds)]) -> usize {
+        let node = self.nodes.len();
+        if let [(i, bounds)] = lights {
+            self.nodes.push(LightNode {
+                bounds: bounds.clone(),
+                content: NodeContent::Leaf(*i),
+            });
+            return node;
+        }
+
+        let bounds = lights[1..]
+            .iter()
+            .fold(lights[0].1.clone(), |b, (_, l)| b.union(l));
+        self.nodes.push(LightNode {
+            bounds,
+            content: NodeContent::Leaf(0),
+        });
+
+        let center = |b: &LightBounds| 0.5 * (b.bbox.min + b.bbox.max);
+        let first = center(&lights[0].1);
+        let (min, max) = lights.iter().fold((first, first), |(min, max), (_, b)| {
+            let c = center(b);
+            (
+                Point3::new(min.x.min(c.x), min.y.min(c.y), min.z.min(c.z)),
+                Point3::new(max.x.max(c.x), max.y.max(c.y), max.z.max(c.z)),
+            )
+        });
+        let spread = max - min;
+        let axis = if spread.x > spread.y && spread.x > spread.z {
+            0
+        } else if spread.y > spread.z {
+            1
+        } else {
+            2
+        };
+        lights.sort_by(|a, b| center(&a.1)[axis].total_cmp(&center(&b.1)[axis]));
+
+        let (left, right) = lights.split_at_mut(lights.len() / 2);
+        self.build(left);
+        let second = self.build(right);
+        self.nodes[node].content = NodeContent::Interior(second);
+        node
+    }
+
+    // odds of picking the tree rather than one of the lights at infinity
+    fn tree_odds(&self) -> f32 {
+        if self.nodes.is_empty() {
+            0.0
+        } else {
+            1.0 / (self.infinite.len() + 1) as f32
+        }
+    }
+
+    // density of picking the lights under `node` with their odds so far, `pmf`
+    fn tree_pdf(&self, node: usize, p: Point3, ray: &Ray, pmf: f32) -> f32 {
+        match self.nodes[node].content {
+            NodeContent::Leaf(i) => pmf * self.lights[i].pdf(p, ray.dir),
+            NodeContent::Interior(second) => {
+                let children = [node + 1, second];
+                let importance = children.map(|c| self.nodes[c].bounds.importance(p));
+                let total = importance[0] + importance[1];
+                if total <= 0.0 {
+                    return 0.0;
+                }
+
+                // lights the ray can't reach have no density in its direction
+                children
+                    .iter()
+                    .zip(importance)
+                    .filter(|&(&c, imp)| {
+                        imp > 0.0 && self.nodes[c].bounds.bbox.hit(ray, 0.0, f32::INFINITY)
+                    })
+                    .map(|(&c, imp)| self.tree_pdf(c, p, ray, pmf * imp / total))
+                    .sum()
+            }
+        }
+    }
+}
+
+impl LightSampler for LightTree {
+    fn pick(&self, p: Point3, u: f32) -> Option<(usize, f32)> {
+        let tree_odds = self.tree_odds();
+        let infinite_odds = 1.0 - tree_odds;
+        if u < infinite_odds && !self.infinite.is_empty() {
+            let n = self.infinite.len();
+            let i = pick_uniform(u / infinite_odds, n);
+            return Some((self.infinite[i], infinite_odds / n as f32));
+        }
+
+        if self.nodes.is_empty() || self.nodes[0].bounds.importance(p) <= 0.0 {
+            return None;
+        }
+
+        // reuse what's left of u at every level
+        let mut u = ((u - infinite_odds) / tree_odds).min(1.0 - f32::EPSILON);
+        let mut pmf = tree_odds;
+        let mut node = 0;
+        loop {
+            match self.nodes[node].content {
+                NodeContent::Leaf(i) => return Some((i, pmf)),
+                NodeContent::Interior(second) => {
+                    let left = self.nodes[node + 1].bounds.importance(p);
+                    let right = self.nodes[second].bounds.importance(p);
+                    if left + right <= 0.0 {
+                        return None;
+                    }
+
+                    let p_left = left / (left + right);
+                    if u < p_left {
+                        u = (u / p_left).min(1.0 - f32::EPSILON);
+                        pmf *= p_left;
+                        node += 1;
+                    } else {
+                        u = ((u - p_left) / (1.0 - p_left)).min(1.0 - f32::EPSILON);
+                        pmf *= 1.0 - p_left;
+                        node = second;
+                    }
+                }
+            }
+        }
+    }
+
+    fn pdf(&self, p: Point3, dir: Vec3) -> f32 {
+        let tree_odds = self.tree_odds();
+        let infinite = if self.infinite.is_empty() {
+            0.0
+        } else {
+            let odds = (1.0 - tree_odds) / self.infinite.len() as f32;
+            self.infinite
+                .iter()
+                .map(|&i| odds * self.lights[i].pdf(p, dir))
+                .sum()
+        };
+
+        if self.nodes.is_empty() || self.nodes[0].bounds.importance(p) <= 0.0 {
+            return infinite;
+        }
+        let ray = Ray::new(p, dir, 0.0);
+        if !self.nodes[0].bounds.bbox.hit(&ray, 0.0, f32::INFINITY) {
+            return infinite;
+        }
+        infinite + self.tree_pdf(0, p, &ray, tree_odds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::AreaLight;
+    use crate::material::DiffuseLight;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColorTexture;
+
+    // centre, radius and brightness of spherical lights of different power, apart from each other
+    const LIGHTS: [([f32; 3], f32, f32); 4] = [
+        ([-4.0, 2.0, 0.0], 0.5, 10.0),
+        ([3.0, 1.0, 2.0], 1.0, 2.0),
+        ([0.0, 5.0, -3.0], 0.3, 40.0),
+        ([2.0, -3.0, -1.0], 0.8, 5.0),
+    ];
+    const POINTS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [-3.0, 2.0, 1.0], [2.0, 4.0, -2.0]];
+    const PICKS: usize = 100_000;
+
+    fn point([x, y, z]: [f32; 3]) -> Point3 {
+        Point3::new(x, y, z)
+    }
+
+    fn lights() -> Vec<Arc<dyn Light>> {
+        LIGHTS
+            .iter()
+            .map(|&(center, radius, emit)| {
+                let material =
+                    DiffuseLight::new(SolidColorTexture::new(Color::new(emit, emit, emit)));
+                let shape = Sphere::new(point(center), radius, material);
+                Arc::new(AreaLight::new(Arc::new(shape))) as Arc<dyn Light>
+            })
+            .collect()
+    }
+
+    // How often `pick` chooses each light, times that light's density for a direction, must add
+    // up to the density the sampler reports for it, or light samples are weighted wrongly.
+    fn check(kind: LightSamplerKind) {
+        let lights = lights();
+        let sampler = kind.create(&lights, 10.0);
+
+        for &p in &POINTS {
+            let p = point(p);
+            let mut counts = vec![0; lights.len()];
+            for k in 0..PICKS {
+                let u = (k as f32 + 0.5) / PICKS as f32;
+                let (i, pmf) = sampler.pick(p, u).unwrap();
+                assert!(
+                    pmf > 0.0,
+                    "{}: light {} picked with odds {}",
+                    kind.name(),
+                    i,
+                    pmf
+                );
+                counts[i] += 1;
+            }
+
+            for &(center, _, _) in &LIGHTS {
+                let dir = point(center) - p;
+                let expected: f32 = lights
+                    .iter()
+                    .zip(&counts)
+                    .map(|(light, &count)| count as f32 / PICKS as f32 * light.pdf(p, dir))
+                    .sum();
+                let pdf = sampler.pdf(p, dir);
+                assert!(
+                    (pdf - expected).abs() <= 1e-3 * expected,
+                    "{}: pdf {} towards {:?} from {:?}, picks give {}",
+                    kind.name(),
+                    pdf,
+                    center,
+                    p,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_pdf_matches_picks() {
+        check(LightSamplerKind::Uniform);
+    }
+
+    #[test]
+    fn power_pdf_matches_picks() {
+        check(LightSamplerKind::Power);
+    }
+
+    #[test]
+    fn tree_pdf_matches_picks() {
+        check(LightSamplerKind::Tree);
+    }
+}
@@ -1,200 +1,267 @@
 pub mod aabb;
 pub mod aarect;
+pub mod adaptive;
 pub mod bvh;
 #[allow(dead_code)]
 pub mod camera;
+pub mod cli;
+pub mod environment;
+pub mod film;
 pub mod gltf;
 pub mod hittable;
+pub mod integrator;
+pub mod light;
+pub mod light_sampler;
 pub mod material;
 pub mod matrix4;
+pub mod microfacet;
 pub mod onb;
+pub mod output;
 pub mod pdf;
 pub mod perlin;
+pub mod progress;
 pub mod ray;
+#[cfg(test)]
+mod regression;
+pub mod render;
+pub mod rng;
+pub mod sampler;
+pub mod scene_file;
 pub mod scenes;
+pub mod spectrum;
 pub mod sphere;
+pub mod state;
 pub mod texture;
 pub mod transforms;
 pub mod triangle;
 pub mod vec3;
 
-use hittable::*;
-use material::*;
-use pdf::*;
-use ray::Ray;
-use vec3::*;
-
-use std::mem;
-use std::ptr;
-
-use rayon::prelude::*;
-use std::sync::{Arc, Mutex};
-
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::LineWriter;
-
-const ASPECT_RATIO: f32 = 1.0;
-const NX: usize = 500;
-const NY: usize = (NX as f32 / ASPECT_RATIO) as usize;
-const SAMPLES_PER_PIXEL: usize = 100;
-const MAX_DEPTH: i32 = 50;
-
-// assumes constructor will never panic. we're safe using just Box::new()
-macro_rules! make_array {
-    ($constructor:expr; $n:expr) => {{
-        let mut items: [_; $n] = mem::MaybeUninit::uninit().assume_init();
-        for place in items.iter_mut() {
-            ptr::write(place, $constructor);
-        }
-        items
-    }};
-}
+use film::Film;
+use output::ImageFormat;
 
-fn main() -> std::io::Result<()> {
-    let (world, cam, background, lights) = scenes::cornell_box(ASPECT_RATIO);
-
-    eprintln!("Rendering!");
-
-    // deterministic and low-discrepancy sequence for MC sims
-    let hx = halton::Sequence::new(2)
-        .map(|x| x as f32)
-        .take(SAMPLES_PER_PIXEL)
-        .collect::<Vec<f32>>();
-    let hy = halton::Sequence::new(3)
-        .map(|x| x as f32)
-        .take(SAMPLES_PER_PIXEL)
-        .collect::<Vec<f32>>();
-
-    /*
-    if world.len() == 1 {
-        println!("P3\n{} {}\n255", NX, NY);
-        (0..NY).into_par_iter().rev().for_each(|y| {
-            eprintln!("Scanlines remaining: {}", y);
-            for x in 0..NX {
-                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-
-                for i in 0..SAMPLES_PER_PIXEL {
-                    let u = (x as f32 + hx[i]) / (NX - 1) as f32;
-                    let v = (y as f32 + hy[i]) / (NY - 1) as f32;
-
-                    let r = cam.get_ray(u, v);
-                    pixel_color += ray_color(r, background, &world[0], &lights[0], MAX_DEPTH);
-                }
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-                image.lock().unwrap()[y as usize][x as usize] =
-                    Vec3::calc_color(pixel_color, SAMPLES_PER_PIXEL);
+fn main() -> std::io::Result<()> {
+    let opts = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli::Command::Render(opts)) => *opts,
+        Ok(cli::Command::ListScenes) => {
+            for name in scenes::names() {
+                println!("{}", name);
             }
-        });
+            return Ok(());
+        }
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("error: {}\nrun with --help to see the available options", e);
+            std::process::exit(2);
+        }
+    };
 
-        eprintln!("Outputting image!");
-        let img = image.lock().unwrap();
-        for y in (0..img.len()).rev() {
-            for x in 0..img[y].len() {
-                println!(
-                    "{} {} {}",
-                    img[y][x].x as u8, img[y][x].y as u8, img[y][x].z as u8
-                );
+    // procedural scenes draw from the same generator, so they come out the same for a seed
+    rng::seed(opts.seed as u64);
+    let (world, cam, background, lights) = match &opts.scene {
+        cli::SceneSource::Builtin(name) => {
+            match scenes::by_name(name).unwrap()(opts.aspect_ratio()) {
+                Ok(scene) => scene,
+                Err(e) => {
+                    eprintln!("error: {}: {}", name, e);
+                    std::process::exit(1);
+                }
             }
         }
-    } else {
-    */
-    for frame in 0..world.len() {
-        let image = unsafe {
-            Arc::new(Mutex::new(Box::new(
-                make_array!( Box::new([Vec3::new_empty(); NX]); NY ),
-            )))
-        };
-
-        (0..NY).into_par_iter().rev().for_each(|y| {
-            eprintln!("Scanlines remaining: {}", y);
-            for x in 0..NX {
-                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+        cli::SceneSource::File(path) => match scene_file::load(path, opts.aspect_ratio()) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("error: {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+    };
+    if lights.len() != world.len() {
+        eprintln!(
+            "error: scene has {} frames but lights for {}",
+            world.len(),
+            lights.len()
+        );
+        std::process::exit(1);
+    }
 
-                for i in 0..SAMPLES_PER_PIXEL {
-                    let u = (x as f32 + hx[i]) / (NX - 1) as f32;
-                    let v = (y as f32 + hy[i]) / (NY - 1) as f32;
+    let settings = render::Settings {
+        width: opts.width,
+        height: opts.height,
+        samples_per_pixel: opts.samples_per_pixel,
+        max_depth: opts.max_depth,
+        max_diffuse: opts.max_diffuse,
+        max_specular: opts.max_specular,
+        max_volume: opts.max_volume,
+        rr_depth: opts.rr_depth,
+        tile_size: opts.tile_size,
+        tile_order: opts.tile_order,
+        sampler: opts.sampler,
+        integrator: opts.integrator,
+        light_sampler: opts.light_sampler,
+        seed: opts.seed,
+        spectral: opts.spectral,
+        progress: true,
+    };
 
-                    let r = cam.get_ray(u, v);
-                    pixel_color +=
-                        ray_color(r, background, &world[frame], &lights[frame], MAX_DEPTH);
-                }
+    for frame in 0..world.len() {
+        let renderer =
+            render::Renderer::new(&settings, &cam, background, &world[frame], &lights[frame]);
+        let label = if world.len() > 1 {
+            format!("frame {}/{}", frame + 1, world.len())
+        } else {
+            "rendering".to_string()
+        };
+        let path = opts.frame_path(frame, world.len());
 
-                image.lock().unwrap()[y as usize][x as usize] =
-                    Vec3::calc_color(pixel_color, SAMPLES_PER_PIXEL);
+        let film = match render_frame(&opts, &renderer, frame, world.len(), &label, &path) {
+            Ok(film) => film,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
             }
-        });
+        };
 
-        eprintln!("Outputting image {}!", frame);
-        let f = File::create(format!("image{:03}.ppm", frame))?;
-        let mut f = LineWriter::new(f);
-        f.write_all(format!("P3\n{} {}\n255\n", NX, NY).as_bytes())?;
-
-        let img = image.lock().unwrap();
-        for y in (0..img.len()).rev() {
-            for x in 0..img[y].len() {
-                f.write_all(
-                    format!(
-                        "{} {} {}\n",
-                        img[y][x].x as u8, img[y][x].y as u8, img[y][x].z as u8
-                    )
-                    .as_bytes(),
-                )?;
-            }
-        }
+        eprintln!("Outputting image {}!", path);
+        film.write(&path, opts.format)?;
     }
-    // }
     Ok(())
 }
 
-fn ray_color(
-    ray: Ray,
-    background: Color,
-    world: &HittableList,
-    lights: &HittableList,
-    depth: i32,
-) -> Color {
-    if depth <= 0 {
-        return Color::new_empty();
+// Renders one frame, continuing from the saved state when resuming. Every pixel carries on
+// from its own sample count, in passes of `pass_spp` samples, or as planned by the adaptive
+// sampler. In progressive mode the image so far is written to `path` after every pass, or every
+// `checkpoint_interval` seconds when that is set, even in the middle of a pass. With a state
+// file the accumulation state is saved after every pass.
+fn render_frame(
+    opts: &cli::Options,
+    renderer: &render::Renderer,
+    frame: usize,
+    frame_count: usize,
+    label: &str,
+    path: &str,
+) -> Result<Film, String> {
+    let spp = opts.samples_per_pixel;
+    let state_path = opts.state_path(frame, frame_count);
+    let hash = match &state_path {
+        Some(_) => state::settings_hash(opts, frame).map_err(|e| e.to_string())?,
+        None => 0,
+    };
+
+    let mut film = Film::new(opts.width, opts.height);
+
+    if let (true, Some(state_path)) = (opts.resume, &state_path) {
+        if Path::new(state_path).exists() {
+            film = state::load(state_path, hash, opts.width, opts.height)
+                .map_err(|e| format!("{}: {}", state_path, e))?;
+            eprintln!(
+                "Resuming {} at {} samples per pixel",
+                state_path,
+                film.samples_taken() as f64 / film.pixels().len() as f64
+            );
+        } else {
+            eprintln!("No saved state at {}, starting from scratch", state_path);
+        }
     }
 
-    match world.hit(&ray, 0.001, std::f32::INFINITY) {
-        Some(hit) => {
-            let emitted = hit.material.emitted(&ray, &hit);
-
-            if let Some(reflection) = hit.material.scatter(&ray, &hit) {
-                match reflection {
-                    ReflectionRecord::Specular {
-                        specular_ray,
-                        attenuation,
-                    } => {
-                        return attenuation
-                            * ray_color(specular_ray, background, world, &lights, depth - 1);
-                    }
-
-                    ReflectionRecord::Scatter {
-                        pdf: reflection_cosine_pdf,
-                        attenuation,
-                    } => {
-                        let light_pdf = HittablePDF::new(hit.p, lights);
-                        let mixture_pdf = MixturePDF::new(&light_pdf, &*reflection_cosine_pdf);
-
-                        let scattered = Ray::new(hit.p, mixture_pdf.generate(), ray.time);
-                        let pdf_val = mixture_pdf.value(scattered.dir);
-
-                        return emitted
-                            + attenuation
-                                * hit.material.scattering_pdf(&ray, &hit, &scattered)
-                                * ray_color(scattered, background, world, &lights, depth - 2)
-                                / pdf_val;
-                    }
+    let adaptive = opts.adaptive();
+    let pass_spp = opts.pass_spp.unwrap_or(spp).min(spp);
+    let min_samples = film.pixels().iter().map(|p| p.samples).min().unwrap_or(0) as usize;
+    let passes = spp.saturating_sub(min_samples).div_ceil(pass_spp);
+    let interval = opts.checkpoint_interval.map(Duration::from_secs);
+    let mut last_checkpoint = Instant::now();
+
+    for pass in 0.. {
+        // number of samples every pixel gets in this pass
+        let (counts, pass_label) = match &adaptive {
+            Some(adaptive) => match adaptive.plan(&film) {
+                Some(counts) => {
+                    let active = counts.iter().filter(|&&c| c > 0).count();
+                    let pass_label = format!("{} adaptive pass {}, {} px", label, pass + 1, active);
+                    (counts, pass_label)
+                }
+                None => break,
+            },
+            None => {
+                if pass == passes {
+                    break;
                 }
+                let counts = film
+                    .pixels()
+                    .iter()
+                    .map(|p| pass_spp.min(spp.saturating_sub(p.samples as usize)) as u32)
+                    .collect();
+                let pass_label = if passes > 1 {
+                    format!("{} pass {}/{}", label, pass + 1, passes)
+                } else {
+                    label.to_string()
+                };
+                (counts, pass_label)
             }
+        };
+
+        let width = film.width;
+        let starts: Vec<u32> = film.pixels().iter().map(|p| p.samples).collect();
+        let samples = |x, row| {
+            let i = row * width + x;
+            starts[i] as usize..(starts[i] + counts[i]) as usize
+        };
 
-            return emitted;
+        renderer.render_pass(&mut film, samples, &pass_label, |film| {
+            if let Some(interval) = interval {
+                if last_checkpoint.elapsed() >= interval {
+                    write_checkpoint(film, path, opts.format);
+                    last_checkpoint = Instant::now();
+                }
+            }
+        });
+
+        if let Some(state_path) = &state_path {
+            state::save(state_path, hash, &film)
+                .map_err(|e| format!("couldn't save render state {}: {}", state_path, e))?;
         }
-        None => {
-            return background;
+
+        // the last uniform pass is written as the final image anyway
+        let last = adaptive.is_none() && pass + 1 == passes;
+        if opts.progressive() && interval.is_none() && !last {
+            write_checkpoint(&film, path, opts.format);
         }
     }
+
+    if let Some(heatmap_path) = opts.heatmap_path(frame, frame_count) {
+        let format = ImageFormat::from_path(&heatmap_path).unwrap_or(opts.format);
+        eprintln!("Outputting sample heatmap {}!", heatmap_path);
+        format
+            .writer()
+            .write(
+                &heatmap_path,
+                film.width,
+                film.height,
+                &adaptive::heatmap(&film),
+            )
+            .map_err(|e| format!("{}: {}", heatmap_path, e))?;
+    }
+
+    Ok(film)
+}
+
+// Checkpoints go to a temporary file that is then renamed over `path`, so an image viewer never
+// sees a half written file. A failed checkpoint is reported but doesn't stop the render.
+fn write_checkpoint(film: &Film, path: &str, format: ImageFormat) {
+    let p = Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let tmp = p.with_file_name(format!("{}.partial.{}", stem, format.extension()));
+
+    let result = film
+        .write(&tmp.to_string_lossy(), format)
+        .and_then(|_| fs::rename(&tmp, p));
+    if let Err(e) = result {
+        eprintln!("\nwarning: couldn't write checkpoint {}: {}", path, e);
+    }
 }
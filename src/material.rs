@@ -1,6 +1,10 @@
 use crate::hittable::HitRecord;
+use crate::microfacet::{dielectric_half_vector, fresnel_dielectric, schlick_fresnel, GGX};
+use crate::onb::ONB;
 use crate::pdf::*;
 use crate::ray::Ray;
+use crate::sampler::{self, Sampler};
+use crate::spectrum::{self, Dispersion};
 use crate::texture::*;
 use crate::vec3::*;
 
@@ -8,7 +12,12 @@ use std::f32::consts::PI;
 use std::sync::Arc;
 
 pub trait Material: Sync + Send {
-    fn scatter(&self, _ray: &Ray, _hr: &HitRecord) -> Option<ReflectionRecord> {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
         None
     }
     fn emitted(&self, _ray: &Ray, _hr: &HitRecord) -> Color {
@@ -17,6 +26,56 @@ pub trait Material: Sync + Send {
     fn scattering_pdf(&self, _ray: &Ray, _hr: &HitRecord, _scattered: &Ray) -> f32 {
         0.0
     }
+    // Light scattered back along `ray` per unit of light arriving along `scattered`, cosine
+    // included, for a `Scatter` record with `attenuation`. Materials whose colour changes with
+    // the directions work it out themselves, the rest scale the attenuation by `scattering_pdf`.
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        attenuation * self.scattering_pdf(ray, hr, scattered)
+    }
+    // whether `emitted` can be anything but black, so shapes made of it are sampled as lights
+    fn is_emitter(&self) -> bool {
+        false
+    }
+    // Fraction of light absorbed per unit of length inside closed shapes made of it, for
+    // materials light goes into. None for those it doesn't, whose surfaces don't take a ray
+    // into or out of anything.
+    fn absorption(&self) -> Option<Color> {
+        None
+    }
+    // tells materials apart for debug output; materials shared through an Arc share an id
+    fn id(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+}
+
+// lets materials picked at runtime, e.g. from scene files, be used by the generic shapes
+impl Material for Arc<dyn Material> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        (**self).scatter(ray, hr, sampler)
+    }
+    fn emitted(&self, ray: &Ray, hr: &HitRecord) -> Color {
+        (**self).emitted(ray, hr)
+    }
+    fn scattering_pdf(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray) -> f32 {
+        (**self).scattering_pdf(ray, hr, scattered)
+    }
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        (**self).scattering(ray, hr, scattered, attenuation)
+    }
+    fn is_emitter(&self) -> bool {
+        (**self).is_emitter()
+    }
+    fn absorption(&self) -> Option<Color> {
+        (**self).absorption()
+    }
+    fn id(&self) -> usize {
+        (**self).id()
+    }
 }
 
 #[derive(Clone)]
@@ -39,10 +98,20 @@ pub enum ReflectionRecord {
         pdf: Arc<dyn PDF>,
         attenuation: Vec3,
     },
+    // scattering inside a participating medium, sampled from the phase function
+    Volume {
+        scattered_ray: Ray,
+        attenuation: Vec3,
+    },
 }
 
 impl<A: Texture> Material for Lambertian<A> {
-    fn scatter(&self, _ray: &Ray, hr: &HitRecord) -> Option<ReflectionRecord> {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
         Some(ReflectionRecord::Scatter {
             pdf: Arc::new(CosinePDF::new(hr.normal)),
             attenuation: self.albedo.value(hr.u, hr.v, hr.p),
@@ -56,6 +125,141 @@ impl<A: Texture> Material for Lambertian<A> {
     }
 }
 
+// the frame of a surface seen from `ray`, with the normal on its side, and the direction back
+// towards where the ray came from
+fn facing_frame(ray: &Ray, hr: &HitRecord) -> (ONB, Vec3) {
+    let wo = -ray.dir.unit_vector();
+    let n = if hr.normal.dot(wo) < 0.0 {
+        -hr.normal
+    } else {
+        hr.normal
+    };
+    (ONB::build_from_w(n), wo)
+}
+
+// Rough diffuse surfaces like clay, plaster or the moon (Oren and Nayar 1994): Lambertian
+// microfacets whose slopes vary by `sigma` degrees. They look flatter than Lambertian ones,
+// and brighter when lit from behind the viewer. A `sigma` of 0 is Lambertian.
+#[derive(Clone)]
+pub struct OrenNayar<A: Texture> {
+    albedo: A,
+    a: f32,
+    b: f32,
+}
+
+impl<A: Texture> OrenNayar<A> {
+    pub fn new(albedo: A, sigma: f32) -> Self {
+        let s2 = sigma.to_radians().powi(2);
+        Self {
+            albedo,
+            a: 1.0 - s2 / (2.0 * (s2 + 0.33)),
+            b: 0.45 * s2 / (s2 + 0.09),
+        }
+    }
+}
+
+impl<A: Texture> Material for OrenNayar<A> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let (uvw, _) = facing_frame(ray, hr);
+        Some(ReflectionRecord::Scatter {
+            pdf: Arc::new(CosinePDF::new(uvw.w)),
+            attenuation: self.albedo.value(hr.u, hr.v, hr.p),
+        })
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray) -> f32 {
+        let (uvw, _) = facing_frame(ray, hr);
+        uvw.w.dot(scattered.dir.unit_vector()).max(0.0) / PI
+    }
+
+    // `attenuation` is the albedo, as given by `scatter`
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        let (uvw, wo) = facing_frame(ray, hr);
+        let wo = uvw.to_local(wo);
+        let wi = uvw.to_local(scattered.dir.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new_empty();
+        }
+
+        let sin_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+        // cosine of the azimuth between the two, none at the normal
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+        // sine of the larger angle from the normal, tangent of the smaller
+        let (sin_alpha, tan_beta) = if wi.z > wo.z {
+            (sin_o, sin_i / wi.z)
+        } else {
+            (sin_i, sin_o / wo.z)
+        };
+
+        wi.z * attenuation / PI * (self.a + self.b * cos_phi * sin_alpha * tan_beta)
+    }
+}
+
+// The diffuse part of the Disney BRDF (Burley 2012, "Physically-Based Shading at Disney"),
+// fit to measured materials: smooth surfaces are darker than Lambertian ones at grazing angles,
+// rough ones brighter, reflecting light back towards where it came from.
+#[derive(Clone)]
+pub struct DisneyDiffuse<A: Texture> {
+    base_color: A,
+    roughness: f32,
+}
+
+impl<A: Texture> DisneyDiffuse<A> {
+    pub fn new(base_color: A, roughness: f32) -> Self {
+        Self {
+            base_color,
+            roughness,
+        }
+    }
+}
+
+impl<A: Texture> Material for DisneyDiffuse<A> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let (uvw, _) = facing_frame(ray, hr);
+        Some(ReflectionRecord::Scatter {
+            pdf: Arc::new(CosinePDF::new(uvw.w)),
+            attenuation: self.base_color.value(hr.u, hr.v, hr.p),
+        })
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray) -> f32 {
+        let (uvw, _) = facing_frame(ray, hr);
+        uvw.w.dot(scattered.dir.unit_vector()).max(0.0) / PI
+    }
+
+    // `attenuation` is the base colour, as given by `scatter`
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        let (uvw, wo) = facing_frame(ray, hr);
+        let wo = uvw.to_local(wo);
+        let wi = uvw.to_local(scattered.dir.unit_vector());
+        let h = wo + wi;
+        if wo.z <= 0.0 || wi.z <= 0.0 || h.near_zero() {
+            return Color::new_empty();
+        }
+
+        let cos_d = wi.dot(h.unit_vector());
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let lobe = |cos: f32| 1.0 + (fd90 - 1.0) * (1.0 - cos).powi(5);
+
+        wi.z * attenuation / PI * lobe(wi.z) * lobe(wo.z)
+    }
+}
+
 #[derive(Clone)]
 pub struct Metal {
     pub albedo: Color,
@@ -72,13 +276,18 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hr: &HitRecord) -> Option<ReflectionRecord> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
         let mut reflected = reflect(ray.dir.unit_vector(), hr.normal);
         if self.fuzz > 0.0 {
-            reflected += self.fuzz * Vec3::random_in_unit_sphere()
+            reflected += self.fuzz * sampler::sample_ball(sampler.get_2d(), sampler.get_1d())
         };
 
-        let attenuation = self.albedo;
+        let attenuation = spectrum::uplift(self.albedo);
 
         if reflected.dot(hr.normal) > 0.0 {
             Some(ReflectionRecord::Specular {
@@ -91,38 +300,166 @@ impl Material for Metal {
     }
 }
 
+// The glTF 2.0 metallic-roughness model. A Lambertian base under a clear coat that reflects
+// 4% at normal incidence, blended towards a bare metal tinted by the base colour as `metallic`
+// goes to 1. Both reflect off GGX microfacets, from mirror-like at roughness 0 to matte at 1.
+#[derive(Clone)]
+pub struct MetallicRoughness<A: Texture> {
+    pub base_color: A,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+// reflectance of the clear coat at normal incidence, that of an index of refraction of 1.5
+const DIELECTRIC_F0: f32 = 0.04;
+
+impl<A: Texture> MetallicRoughness<A> {
+    pub fn new(base_color: A, metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+        }
+    }
+
+    // the surface's frame, with the normal on the side `ray` comes from, and the direction
+    // back towards where it came from
+    fn frame(ray: &Ray, hr: &HitRecord) -> (ONB, Vec3) {
+        facing_frame(ray, hr)
+    }
+
+    // Sampling density that picks the reflection in proportion to how much of the light it
+    // takes at this angle, and the diffuse base otherwise.
+    fn pdf(&self, base: Color, normal: Vec3, wo: Vec3) -> GGXPDF {
+        let cos_o = normal.dot(wo).max(0.0);
+        let f0 = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        let coat = schlick_fresnel(f0, cos_o).x;
+        let metal = schlick_fresnel(base, cos_o).luminance();
+
+        let specular = (1.0 - self.metallic) * coat + self.metallic * metal;
+        let diffuse = (1.0 - self.metallic) * (1.0 - coat) * base.luminance();
+        let specular_odds = if specular + diffuse > 0.0 {
+            specular / (specular + diffuse)
+        } else {
+            1.0
+        };
+
+        GGXPDF::new(
+            normal,
+            wo,
+            GGX::from_roughness(self.roughness),
+            specular_odds,
+        )
+    }
+}
+
+impl<A: Texture> Material for MetallicRoughness<A> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let base = self.base_color.value(hr.u, hr.v, hr.p);
+        let (uvw, wo) = Self::frame(ray, hr);
+        Some(ReflectionRecord::Scatter {
+            pdf: Arc::new(self.pdf(base, uvw.w, wo)),
+            attenuation: base,
+        })
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray) -> f32 {
+        let base = self.base_color.value(hr.u, hr.v, hr.p);
+        let (uvw, wo) = Self::frame(ray, hr);
+        self.pdf(base, uvw.w, wo).value(scattered.dir)
+    }
+
+    // `attenuation` is the base colour, as given by `scatter`
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        let (uvw, wo) = Self::frame(ray, hr);
+        let wo = uvw.to_local(wo);
+        let wi = uvw.to_local(scattered.dir.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new_empty();
+        }
+
+        let ggx = GGX::from_roughness(self.roughness);
+        let h = (wo + wi).unit_vector();
+        let cos_h = wo.dot(h).max(0.0);
+        let specular = ggx.d(h) * ggx.visibility(wo, wi);
+
+        let grey = |v: f32| Color::new(v, v, v);
+        let coat = schlick_fresnel(grey(DIELECTRIC_F0), cos_h);
+        let dielectric = (grey(1.0) - coat) * attenuation / PI + specular * coat;
+        let metal = specular * schlick_fresnel(attenuation, cos_h);
+
+        wi.z * ((1.0 - self.metallic) * dielectric + self.metallic * metal)
+    }
+}
+
+// Smooth glass. Clear unless given an `absorption`, which darkens light in proportion to the
+// distance it travels inside, so thick parts look deeper in colour than thin ones. With a
+// `dispersion` the index of refraction depends on the wavelength, and paths through it follow
+// a single one, splitting white light into a rainbow like prisms and diamonds do.
 #[derive(Clone)]
 pub struct Dieletric {
     ir: f32,
+    absorption: Color,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dieletric {
     pub fn new(index_of_refraction: f32) -> Self {
+        Self::with_absorption(index_of_refraction, Color::new_empty())
+    }
+
+    pub fn with_absorption(index_of_refraction: f32, absorption: Color) -> Self {
         Self {
             ir: index_of_refraction,
+            absorption,
+            dispersion: None,
+        }
+    }
+
+    pub fn with_dispersion(self, dispersion: Dispersion) -> Self {
+        Self {
+            ir: dispersion.ior_d(),
+            dispersion: Some(dispersion),
+            ..self
         }
     }
 }
 
 impl Material for Dieletric {
-    fn scatter(&self, ray: &Ray, hr: &HitRecord) -> Option<ReflectionRecord> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
         let outward_normal: Vec3;
         let ni_over_nt: f32;
         let cosine: f32;
         let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let ir = match self.dispersion {
+            Some(dispersion) => dispersion.ior(spectrum::hero_wavelength(sampler)),
+            None => self.ir,
+        };
 
-        if ray.dir.dot(hr.normal) > 0.0 {
-            outward_normal = -hr.normal;
-            ni_over_nt = self.ir;
-            cosine = self.ir * ray.dir.dot(hr.normal) / ray.dir.length();
+        // shapes may turn the normal towards the ray, going by `front_face`
+        let normal = if hr.front_face { hr.normal } else { -hr.normal };
+        if ray.dir.dot(normal) > 0.0 {
+            outward_normal = -normal;
+            ni_over_nt = ir;
+            cosine = ir * ray.dir.dot(normal) / ray.dir.length();
         } else {
-            outward_normal = hr.normal;
-            ni_over_nt = 1.0 / self.ir;
-            cosine = -ray.dir.dot(hr.normal) / ray.dir.length();
+            outward_normal = normal;
+            ni_over_nt = 1.0 / ir;
+            cosine = -ray.dir.dot(normal) / ray.dir.length();
         }
 
         if let Some(refraction) = refract(ray.dir, outward_normal, ni_over_nt) {
-            if rand::random::<f32>() > schlick(cosine, self.ir) {
+            if sampler.get_1d() > schlick(cosine, ir) {
                 let refraction = Ray::new(hr.p, refraction, ray.time);
                 return Some(ReflectionRecord::Specular {
                     specular_ray: refraction,
@@ -143,6 +480,161 @@ impl Material for Dieletric {
             attenuation,
         })
     }
+
+    fn absorption(&self) -> Option<Color> {
+        Some(spectrum::uplift(self.absorption))
+    }
+}
+
+// The absorption that leaves `color` of the light after `distance` inside a medium, the way
+// glTF and most tools describe coloured glass and liquids.
+pub fn absorption_at_distance(color: Color, distance: f32) -> Color {
+    let channel = |c: f32| {
+        if c >= 1.0 || !(distance > 0.0 && distance.is_finite()) {
+            0.0
+        } else {
+            -c.max(1e-6).ln() / distance
+        }
+    };
+    Color::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+// Glass with a rough surface, from frosted to polished: light reflects off or goes through
+// GGX microfacets, bending at each one. `tint` filters the light every time it goes through the
+// surface, so a solid object made of it is tinted on the way in and again on the way out, and
+// `absorption` along the way in between, like `Dieletric`'s.
+#[derive(Clone)]
+pub struct RoughDielectric<A: Texture> {
+    pub ir: f32,
+    pub roughness: f32,
+    pub tint: A,
+    pub absorption: Color,
+}
+
+impl<A: Texture> RoughDielectric<A> {
+    pub fn new(index_of_refraction: f32, roughness: f32, tint: A, absorption: Color) -> Self {
+        Self {
+            ir: index_of_refraction,
+            roughness: roughness.clamp(0.0, 1.0),
+            tint,
+            absorption,
+        }
+    }
+
+    // the surface's frame, with the normal on the side `ray` comes from, the direction back
+    // towards where it came from and the index of refraction of the other side over this one's
+    fn frame(&self, ray: &Ray, hr: &HitRecord) -> (ONB, Vec3, f32) {
+        let wo = -ray.dir.unit_vector();
+        let outward = if hr.front_face { hr.normal } else { -hr.normal };
+        if outward.dot(wo) >= 0.0 {
+            (ONB::build_from_w(outward), wo, self.ir)
+        } else {
+            (ONB::build_from_w(-outward), wo, 1.0 / self.ir)
+        }
+    }
+}
+
+impl<A: Texture> Material for RoughDielectric<A> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let (uvw, wo, eta) = self.frame(ray, hr);
+        Some(ReflectionRecord::Scatter {
+            pdf: Arc::new(RoughDielectricPDF::new(
+                uvw.w,
+                wo,
+                GGX::from_roughness(self.roughness),
+                eta,
+            )),
+            attenuation: self.tint.value(hr.u, hr.v, hr.p),
+        })
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray) -> f32 {
+        let (uvw, wo, eta) = self.frame(ray, hr);
+        RoughDielectricPDF::new(uvw.w, wo, GGX::from_roughness(self.roughness), eta)
+            .value(scattered.dir)
+    }
+
+    // `attenuation` is the tint, as given by `scatter`
+    fn scattering(&self, ray: &Ray, hr: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        let (uvw, wo, eta) = self.frame(ray, hr);
+        let wo = uvw.to_local(wo);
+        let wi = uvw.to_local(scattered.dir.unit_vector());
+        let (h, _) = match dielectric_half_vector(wo, wi, eta) {
+            Some(h) => h,
+            None => return Color::new_empty(),
+        };
+
+        let ggx = GGX::from_roughness(self.roughness);
+        let reflectance = fresnel_dielectric(wo.dot(h), eta);
+        if wi.z > 0.0 {
+            let f = ggx.d(h) * ggx.g(wo, wi) * reflectance / (4.0 * wo.z * wi.z);
+            return Color::new(f, f, f) * wi.z;
+        }
+
+        // radiance is squeezed into a narrower cone going into the denser side, hence the
+        // division by eta squared
+        let denom = wi.dot(h) + wo.dot(h) / eta;
+        let f = ggx.d(h) * ggx.g(wo, wi) * (1.0 - reflectance) * (wi.dot(h) * wo.dot(h)).abs()
+            / (denom * denom * wo.z * wi.z.abs() * eta * eta);
+        f * wi.z.abs() * attenuation
+    }
+
+    fn absorption(&self) -> Option<Color> {
+        Some(spectrum::uplift(self.absorption))
+    }
+}
+
+// A sheet of glass too thin to bend the light going through it, like a window pane: it either
+// reflects off the sheet, counting the bounces between its two faces, or goes straight on,
+// filtered by `tint`.
+#[derive(Clone)]
+pub struct ThinDielectric<A: Texture> {
+    pub ir: f32,
+    pub tint: A,
+}
+
+impl<A: Texture> ThinDielectric<A> {
+    pub fn new(index_of_refraction: f32, tint: A) -> Self {
+        Self {
+            ir: index_of_refraction,
+            tint,
+        }
+    }
+}
+
+impl<A: Texture> Material for ThinDielectric<A> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let dir = ray.dir.unit_vector();
+        let mut reflectance = fresnel_dielectric(dir.dot(hr.normal).abs(), self.ir);
+        // light that gets through the first face bounces back and forth inside the sheet
+        if reflectance < 1.0 {
+            let transmittance = 1.0 - reflectance;
+            reflectance +=
+                transmittance * transmittance * reflectance / (1.0 - reflectance * reflectance);
+        }
+
+        if sampler.get_1d() < reflectance {
+            Some(ReflectionRecord::Specular {
+                specular_ray: Ray::new(hr.p, reflect(dir, hr.normal), ray.time),
+                attenuation: Color::new(1.0, 1.0, 1.0),
+            })
+        } else {
+            Some(ReflectionRecord::Specular {
+                specular_ray: Ray::new(hr.p, dir, ray.time),
+                attenuation: self.tint.value(hr.u, hr.v, hr.p),
+            })
+        }
+    }
 }
 
 pub fn reflect(m: Vec3, n: Vec3) -> Vec3 {
@@ -185,6 +677,9 @@ impl<A: Texture> Material for DiffuseLight<A> {
             Color::new_empty()
         }
     }
+    fn is_emitter(&self) -> bool {
+        true
+    }
 }
 
 /*
@@ -220,12 +715,17 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, ray: &Ray, hr: &HitRecord) -> Option<ReflectionRecord> {
-        let specular_ray = Ray::new(hr.p, Vec3::random_in_unit_sphere(), ray.time);
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hr: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ReflectionRecord> {
+        let scattered_ray = Ray::new(hr.p, sampler::sample_sphere(sampler.get_2d()), ray.time);
         let attenuation = self.albedo.value(hr.u, hr.v, hr.p);
 
-        Some(ReflectionRecord::Specular {
-            specular_ray,
+        Some(ReflectionRecord::Volume {
+            scattered_ray,
             attenuation,
         })
     }
@@ -110,6 +110,24 @@ impl Matrix4 {
         Some(dst)
     }
 
+    pub fn transpose(&self) -> Self {
+        let mut r = Matrix4([[0.0; 4]; 4]);
+        for i in 0..4 {
+            for j in 0..4 {
+                r.0[i][j] = self.0[j][i];
+            }
+        }
+        r
+    }
+
+    // determinant of the upper left 3x3, how much the matrix scales volumes
+    pub fn determinant_33(&self) -> f32 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
     pub fn mul_as_33(&self, other: Vec3) -> Vec3 {
         Vec3::new(
             self.0[0][0] * other.x + self.0[0][1] * other.y + self.0[0][2] * other.z,
@@ -0,0 +1,180 @@
+use crate::vec3::*;
+
+use std::f32::consts::PI;
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals with Smith shadowing, for
+// rough surfaces made of tiny mirrors. Directions are in the local frame of the surface, with
+// the normal along +z.
+#[derive(Debug, Clone, Copy)]
+pub struct GGX {
+    pub alpha: f32,
+}
+
+// below this the distribution is too sharp for f32, and is as good as a mirror anyway
+const MIN_ALPHA: f32 = 1e-3;
+
+impl GGX {
+    // glTF and most tools take a perceptual roughness, alpha is its square
+    pub fn from_roughness(roughness: f32) -> Self {
+        Self {
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+        }
+    }
+
+    // density of microfacet normals `h`, per unit of projected area
+    pub fn d(&self, h: Vec3) -> f32 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let t = h.z * h.z * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    // fraction of the microfacets facing `w` that `w` sees, the Smith masking term
+    pub fn g1(&self, w: Vec3) -> f32 {
+        let cos = w.z.abs();
+        let a2 = self.alpha * self.alpha;
+        2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+    }
+
+    // masked microfacet area per unit of visible area, seen from `w`
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    // fraction of the microfacets seen from both `wo` and `wi`, height correlated
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // masking and shadowing of the pair, height correlated, folded together with the
+    // 1 / (4 cos_o cos_i) of the microfacet BRDF
+    pub fn visibility(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let (no, ni) = (wo.z.abs(), wi.z.abs());
+        let a2 = self.alpha * self.alpha;
+        let denom =
+            ni * (no * no * (1.0 - a2) + a2).sqrt() + no * (ni * ni * (1.0 - a2) + a2).sqrt();
+        if denom > 0.0 {
+            0.5 / denom
+        } else {
+            0.0
+        }
+    }
+
+    // A microfacet normal as seen from `wo`, in proportion to its visible area (Heitz 2018,
+    // "Sampling the GGX Distribution of Visible Normals"). Fewer samples are wasted on facets
+    // that face away from `wo` than when sampling `d` itself.
+    pub fn sample_visible_normal(&self, wo: Vec3, u: (f32, f32)) -> Vec3 {
+        // stretch to the hemisphere configuration
+        let vh = Vec3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).unit_vector();
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // a point on the projected disk, squeezed towards the visible half
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(0.0)).unit_vector()
+    }
+
+    // density of `sample_visible_normal` picking `h` from `wo`
+    pub fn visible_normal_pdf(&self, wo: Vec3, h: Vec3) -> f32 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z
+    }
+
+    // density of reflecting `wo` about a visible normal and ending up along `wi`
+    pub fn reflection_pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let h = wo + wi;
+        if wi.z <= 0.0 || h.near_zero() {
+            return 0.0;
+        }
+        let h = h.unit_vector();
+        self.visible_normal_pdf(wo, h) / (4.0 * wo.dot(h).abs())
+    }
+}
+
+// The microfacet normal of a boundary between dielectrics that turns `wo` into `wi`, either by
+// reflection or by refraction into a medium with relative index of refraction `eta`, and the
+// density of `wi` per density of that normal. Both directions are in the local frame with `wo`
+// above the surface. None for pairs no microfacet facing `wo` connects.
+pub fn dielectric_half_vector(wo: Vec3, wi: Vec3, eta: f32) -> Option<(Vec3, f32)> {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return None;
+    }
+    let reflect = wi.z > 0.0;
+    let h = if reflect { wo + wi } else { wo + eta * wi };
+    if h.near_zero() {
+        return None;
+    }
+    let h = if h.z < 0.0 { -h } else { h }.unit_vector();
+
+    // microfacets seen from behind
+    if wo.dot(h) <= 0.0 || (wi.dot(h) <= 0.0) == reflect {
+        return None;
+    }
+
+    let jacobian = if reflect {
+        1.0 / (4.0 * wo.dot(h))
+    } else {
+        let denom = wi.dot(h) + wo.dot(h) / eta;
+        wi.dot(h).abs() / (denom * denom)
+    };
+    Some((h, jacobian))
+}
+
+// Schlick's approximation of the Fresnel reflectance, from the reflectance at normal incidence
+pub fn schlick_fresnel(f0: Color, cos_theta: f32) -> Color {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + m * (Color::new(1.0, 1.0, 1.0) - f0)
+}
+
+// Fraction of light reflected by a smooth boundary into a medium with relative index of
+// refraction `eta`, arriving at `cos_theta` from the normal. Past the critical angle all of it.
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta.clamp(-1.0, 1.0);
+    // arriving from inside
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// `w` bent through a boundary with normal `n` on its side, into a medium with relative index
+// of refraction `eta`. None past the critical angle.
+pub fn refract(w: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = n.dot(w);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * n)
+}
@@ -29,6 +29,11 @@ impl ONB {
         a.x * self.u + a.y * self.v + a.z * self.w
     }
 
+    // the inverse of `local_vec3`: `a` in this basis
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
     pub fn build_from_w(n: Vec3) -> Self {
         let w = n.unit_vector();
         let a = if w.x.abs() > 0.9 {
@@ -0,0 +1,126 @@
+use crate::vec3::*;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+// Writers get the linear radiance of every pixel, rows from top to bottom. 8 bit formats are
+// gamma corrected and clamped here, floating point formats store the radiance untouched.
+pub trait ImageWriter {
+    fn write(&self, path: &str, width: usize, height: usize, pixels: &[Color]) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Hdr,
+    Exr,
+}
+
+impl ImageFormat {
+    pub const NAMES: &'static str = "ppm, png, hdr, exr";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    // format matching the extension of `path`
+    pub fn from_path(path: &str) -> Option<Self> {
+        Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_name)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+            ImageFormat::Hdr => "hdr",
+            ImageFormat::Exr => "exr",
+        }
+    }
+
+    pub fn writer(&self) -> Box<dyn ImageWriter> {
+        match self {
+            ImageFormat::Ppm => Box::new(PpmWriter),
+            ImageFormat::Png => Box::new(PngWriter),
+            ImageFormat::Hdr => Box::new(HdrWriter),
+            ImageFormat::Exr => Box::new(ExrWriter),
+        }
+    }
+}
+
+// ASCII P3 pixmap
+pub struct PpmWriter;
+
+impl ImageWriter for PpmWriter {
+    fn write(&self, path: &str, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+        let mut f = BufWriter::new(File::create(path)?);
+        write!(f, "P3\n{} {}\n255\n", width, height)?;
+
+        for pixel in pixels {
+            let c = Vec3::calc_color(*pixel, 1);
+            writeln!(f, "{} {} {}", c.x as u8, c.y as u8, c.z as u8)?;
+        }
+
+        f.flush()
+    }
+}
+
+pub struct PngWriter;
+
+impl ImageWriter for PngWriter {
+    fn write(&self, path: &str, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(pixels.len() * 3);
+        for pixel in pixels {
+            let c = Vec3::calc_color(*pixel, 1);
+            buf.extend_from_slice(&[c.x as u8, c.y as u8, c.z as u8]);
+        }
+
+        image::save_buffer(
+            path,
+            &buf,
+            width as u32,
+            height as u32,
+            image::ColorType::RGB(8),
+        )
+    }
+}
+
+// Radiance RGBE
+pub struct HdrWriter;
+
+impl ImageWriter for HdrWriter {
+    fn write(&self, path: &str, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+        let data: Vec<image::Rgb<f32>> = pixels
+            .iter()
+            .map(|p| image::Rgb([p.x.max(0.0), p.y.max(0.0), p.z.max(0.0)]))
+            .collect();
+
+        let f = BufWriter::new(File::create(path)?);
+        image::hdr::HDREncoder::new(f).encode(&data, width, height)
+    }
+}
+
+// OpenEXR, 32 bit float RGB
+pub struct ExrWriter;
+
+impl ImageWriter for ExrWriter {
+    fn write(&self, path: &str, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+        exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let p = pixels[y * width + x];
+            (p.x, p.y, p.z)
+        })
+        .map_err(io::Error::other)
+    }
+}
@@ -1,12 +1,14 @@
 use crate::hittable::*;
+use crate::microfacet::{dielectric_half_vector, fresnel_dielectric, refract, GGX};
 use crate::onb::ONB;
+use crate::sampler::{self, Sampler};
 use crate::vec3::*;
 
 use std::f32::consts;
 
 pub trait PDF {
     fn value(&self, dir: Vec3) -> f32;
-    fn generate(&self) -> Vec3;
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
 }
 
 pub struct CosinePDF {
@@ -31,8 +33,114 @@ impl PDF for CosinePDF {
         }
     }
 
-    fn generate(&self) -> Vec3 {
-        self.uvw.local_vec3(Vec3::random_cosine_dir())
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw
+            .local_vec3(sampler::sample_cosine_hemisphere(sampler.get_2d()))
+    }
+}
+
+// ---------------------------------------------------------------
+
+// Reflection off GGX microfacets, mixed with the cosine lobe of a diffuse base underneath:
+// materials pick the reflection with `specular_odds`, going by how much each lobe reflects.
+pub struct GGXPDF {
+    uvw: ONB,
+    // towards the viewer, in the local frame of `uvw`
+    wo: Vec3,
+    ggx: GGX,
+    specular_odds: f32,
+}
+
+impl GGXPDF {
+    pub fn new(normal: Vec3, wo: Vec3, ggx: GGX, specular_odds: f32) -> Self {
+        let uvw = ONB::build_from_w(normal);
+        Self {
+            wo: uvw.to_local(wo.unit_vector()),
+            uvw,
+            ggx,
+            specular_odds,
+        }
+    }
+}
+
+impl PDF for GGXPDF {
+    fn value(&self, dir: Vec3) -> f32 {
+        let wi = self.uvw.to_local(dir.unit_vector());
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let specular = self.ggx.reflection_pdf(self.wo, wi);
+        let diffuse = wi.z / consts::PI;
+        self.specular_odds * specular + (1.0 - self.specular_odds) * diffuse
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let specular = sampler.get_1d() < self.specular_odds;
+        let u = sampler.get_2d();
+        if specular {
+            let h = self.ggx.sample_visible_normal(self.wo, u);
+            self.uvw.local_vec3(2.0 * self.wo.dot(h) * h - self.wo)
+        } else {
+            self.uvw.local_vec3(sampler::sample_cosine_hemisphere(u))
+        }
+    }
+}
+
+// ---------------------------------------------------------------
+
+// Reflection off and transmission through the GGX microfacets of a boundary between two
+// dielectrics (Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces").
+// A visible normal is picked and the light either reflects off it or goes through, in
+// proportion to the Fresnel reflectance there.
+pub struct RoughDielectricPDF {
+    uvw: ONB,
+    // towards the viewer, in the local frame of `uvw`, whose normal is on the viewer's side
+    wo: Vec3,
+    ggx: GGX,
+    // index of refraction of the far side over that of the viewer's side
+    eta: f32,
+}
+
+impl RoughDielectricPDF {
+    pub fn new(normal: Vec3, wo: Vec3, ggx: GGX, eta: f32) -> Self {
+        let uvw = ONB::build_from_w(normal);
+        Self {
+            wo: uvw.to_local(wo.unit_vector()),
+            uvw,
+            ggx,
+            eta,
+        }
+    }
+}
+
+impl PDF for RoughDielectricPDF {
+    fn value(&self, dir: Vec3) -> f32 {
+        let wi = self.uvw.to_local(dir.unit_vector());
+        match dielectric_half_vector(self.wo, wi, self.eta) {
+            Some((h, jacobian)) => {
+                let reflectance = fresnel_dielectric(self.wo.dot(h), self.eta);
+                let odds = if wi.z > 0.0 {
+                    reflectance
+                } else {
+                    1.0 - reflectance
+                };
+                self.ggx.visible_normal_pdf(self.wo, h) * jacobian * odds
+            }
+            None => 0.0,
+        }
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let reflect = sampler.get_1d();
+        let h = self.ggx.sample_visible_normal(self.wo, sampler.get_2d());
+        let reflectance = fresnel_dielectric(self.wo.dot(h), self.eta);
+        let wi = if reflect < reflectance {
+            None
+        } else {
+            refract(self.wo, h, self.eta)
+        };
+        self.uvw
+            .local_vec3(wi.unwrap_or_else(|| 2.0 * self.wo.dot(h) * h - self.wo))
     }
 }
 
@@ -55,8 +163,8 @@ impl PDF for HittablePDF<'_> {
         self.hit.pdf_value(self.orig, dir)
     }
 
-    fn generate(&self) -> Vec3 {
-        self.hit.random(self.orig)
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.hit.random(self.orig, sampler)
     }
 }
 
@@ -79,24 +187,42 @@ impl PDF for MixturePDF<'_> {
         0.5 * self.p0.value(dir) + 0.5 * self.p1.value(dir)
     }
 
-    fn generate(&self) -> Vec3 {
-        if rand::random::<f32>() < 0.5 {
-            self.p0.generate()
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if sampler.get_1d() < 0.5 {
+            self.p0.generate(sampler)
         } else {
-            self.p1.generate()
+            self.p1.generate(sampler)
         }
     }
 }
 
-pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
-    let r1 = rand::random::<f32>();
-    let r2 = rand::random::<f32>();
+// direction towards a sphere of `radius` at `distance_squared`, uniform over the cone it
+// subtends, around +z. From inside the sphere every direction leads to it, so they all are.
+pub fn random_to_sphere(radius: f32, distance_squared: f32, u: (f32, f32)) -> Vec3 {
+    let (r1, r2) = u;
 
-    let z = 1.0 + r2 * ((1.0 - radius.powi(2) / distance_squared).sqrt() - 1.0);
+    let z = 1.0 - r2 * one_minus_cos_theta_max(radius, distance_squared);
     let phi = 2.0 * consts::PI * r1;
 
-    let x = phi.cos() * (1.0 - z.powi(2)).sqrt();
-    let y = phi.cos() * (1.0 - z.powi(2)).sqrt();
+    let x = phi.cos() * (1.0 - z.powi(2)).max(0.0).sqrt();
+    let y = phi.sin() * (1.0 - z.powi(2)).max(0.0).sqrt();
 
     Vec3::new(x, y, z)
 }
+
+// solid angle of the cone `random_to_sphere` picks directions from
+pub fn sphere_solid_angle(radius: f32, distance_squared: f32) -> f32 {
+    2.0 * consts::PI * one_minus_cos_theta_max(radius, distance_squared)
+}
+
+// 1 - cos of the half angle of the cone a sphere subtends, or 2 from inside it, where the cone
+// is the whole sphere of directions. Written as sin² / (1 + cos) so that small, distant spheres
+// don't round it to zero.
+fn one_minus_cos_theta_max(radius: f32, distance_squared: f32) -> f32 {
+    let sin2 = radius.powi(2) / distance_squared;
+    if sin2 >= 1.0 {
+        2.0
+    } else {
+        sin2 / (1.0 + (1.0 - sin2).sqrt())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rng;
 use crate::vec3::{Point3, Vec3};
 use rand::prelude::*;
 
@@ -87,7 +88,7 @@ fn perlin_generate_perm() -> Vec<usize> {
         p.push(i);
     }
 
-    let mut rng = rand::thread_rng();
+    let mut rng = rng::local();
 
     for i in (1..POINT_COUNT).rev() {
         let target = rng.gen_range(0..i);
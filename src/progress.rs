@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// Single line progress bar on stderr, redrawn in place with a carriage return.
+pub struct Progress {
+    label: String,
+    total: u64,
+    done: u64,
+    rays: u64,
+    start: Instant,
+    last_draw: Option<Instant>,
+    hidden: bool,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: u64) -> Self {
+        Self {
+            label: label.to_string(),
+            total: total.max(1),
+            done: 0,
+            rays: 0,
+            start: Instant::now(),
+            last_draw: None,
+            hidden: false,
+        }
+    }
+
+    // keeps count without drawing anything, for renders nobody is watching
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    pub fn advance(&mut self, work: u64, rays: u64) {
+        self.done = (self.done + work).min(self.total);
+        self.rays += rays;
+
+        let now = Instant::now();
+        let due = match self.last_draw {
+            Some(t) => now.duration_since(t) >= REDRAW_INTERVAL,
+            None => true,
+        };
+        if due || self.done == self.total {
+            self.last_draw = Some(now);
+            self.draw(false);
+        }
+    }
+
+    // draws the final state and moves to the next line
+    pub fn finish(&mut self) {
+        self.draw(true);
+    }
+
+    fn draw(&self, last: bool) {
+        if self.hidden {
+            return;
+        }
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let fraction = self.done as f64 / self.total as f64;
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+        let rays_per_sec = if elapsed > 0.0 {
+            self.rays as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        let time = if last {
+            format!("in {}", format_duration(elapsed))
+        } else if self.done > 0 {
+            let remaining = elapsed * (self.total - self.done) as f64 / self.done as f64;
+            format!("ETA {}", format_duration(remaining))
+        } else {
+            "ETA --:--".to_string()
+        };
+
+        let mut err = io::stderr();
+        // trailing spaces clear leftovers of a longer previous line
+        let _ = write!(
+            err,
+            "\r{} [{}{}] {:5.1}%  {}  {}   ",
+            self.label,
+            "=".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            fraction * 100.0,
+            format_rate(rays_per_sec),
+            time
+        );
+        if last {
+            let _ = writeln!(err);
+        }
+        let _ = err.flush();
+    }
+}
+
+fn format_rate(rays_per_sec: f64) -> String {
+    if rays_per_sec >= 1e6 {
+        format!("{:.2} Mrays/s", rays_per_sec / 1e6)
+    } else if rays_per_sec >= 1e3 {
+        format!("{:.1} krays/s", rays_per_sec / 1e3)
+    } else {
+        format!("{:.0} rays/s", rays_per_sec)
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,191 @@
+// Image regression tests: small renders of the builtin scenes and of the scene files in scenes/
+// with a fixed seed, compared against the references in tests/references. A render that drifts
+// too far from its reference fails the test and leaves the render and a difference image in
+// target/regression.
+//
+// After a change that is meant to alter the images, regenerate the references with
+//
+//     UPDATE_REFERENCES=1 cargo test regression
+//
+// and look at them before committing.
+
+use crate::integrator::IntegratorKind;
+use crate::light_sampler::LightSamplerKind;
+use crate::output::{ImageFormat, ImageWriter, PngWriter};
+use crate::render::{Renderer, Settings, TileOrder};
+use crate::rng;
+use crate::sampler::SamplerKind;
+use crate::scene_file;
+use crate::scenes::{self, Scene};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SIZE: usize = 64;
+const SAMPLES: usize = 32;
+const MAX_DEPTH: i32 = 10;
+const SEED: u32 = 0;
+
+// The renderer is deterministic, so on the same platform a render matches its reference
+// exactly. The margin is for floating point differences between compilers and targets, and is
+// well above what an unchanged renderer drifts by but below the noise of a different sample
+// sequence at this sample count.
+const MIN_PSNR: f64 = 40.0;
+
+// differences are this many times brighter in the diff image
+const DIFF_GAIN: f64 = 8.0;
+
+fn check_builtin(scene: &str) {
+    rng::seed(SEED as u64);
+    check(scene, scenes::by_name(scene).unwrap()(1.0).unwrap());
+}
+
+fn check_file(scene: &str) {
+    rng::seed(SEED as u64);
+    let path = root().join("scenes").join(format!("{}.toml", scene));
+    let loaded = scene_file::load(path.to_str().unwrap(), 1.0)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    check(scene, loaded);
+}
+
+fn check(scene: &str, (world, cam, background, lights): Scene) {
+    let settings = Settings {
+        width: SIZE,
+        height: SIZE,
+        samples_per_pixel: SAMPLES,
+        max_depth: MAX_DEPTH,
+        max_diffuse: MAX_DEPTH,
+        max_specular: MAX_DEPTH,
+        max_volume: MAX_DEPTH,
+        rr_depth: 3,
+        tile_size: 16,
+        tile_order: TileOrder::Scanline,
+        sampler: SamplerKind::Sobol,
+        integrator: IntegratorKind::Path,
+        light_sampler: LightSamplerKind::Tree,
+        seed: SEED,
+        spectral: false,
+        progress: false,
+    };
+    let film = Renderer::new(&settings, &cam, background, &world[0], &lights[0]).render(scene);
+
+    // compare what gets written, 8 bit and gamma corrected, so the references are plain PNGs
+    let out_dir = root().join("target").join("regression");
+    fs::create_dir_all(&out_dir).unwrap();
+    let rendered_path = out_dir.join(format!("{}.png", scene));
+    film.write(rendered_path.to_str().unwrap(), ImageFormat::Png)
+        .unwrap();
+    let rendered = read_rgb(&rendered_path);
+
+    let reference_path = root()
+        .join("tests")
+        .join("references")
+        .join(format!("{}.png", scene));
+    if std::env::var_os("UPDATE_REFERENCES").is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        fs::copy(&rendered_path, &reference_path).unwrap();
+        return;
+    }
+    if !reference_path.exists() {
+        panic!(
+            "{}: no reference at {}, run with UPDATE_REFERENCES=1 to create it",
+            scene,
+            reference_path.display()
+        );
+    }
+    let reference = read_rgb(&reference_path);
+    assert_eq!(
+        rendered.len(),
+        reference.len(),
+        "{}: reference has a different size",
+        scene
+    );
+
+    let rmse = rmse(&rendered, &reference);
+    let psnr = psnr(rmse);
+    if psnr < MIN_PSNR {
+        let diff_path = out_dir.join(format!("{}-diff.png", scene));
+        write_diff(&diff_path, &rendered, &reference);
+        panic!(
+            "{}: RMSE {:.3}, PSNR {:.1} dB, below {} dB; render in {}, difference in {}",
+            scene,
+            rmse,
+            psnr,
+            MIN_PSNR,
+            rendered_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn read_rgb(path: &Path) -> Vec<u8> {
+    image::open(path)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        .to_rgb()
+        .into_raw()
+}
+
+// root mean square error over all channels, in 8 bit steps
+fn rmse(a: &[u8], b: &[u8]) -> f64 {
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum();
+    (sum / a.len() as f64).sqrt()
+}
+
+fn psnr(rmse: f64) -> f64 {
+    if rmse == 0.0 {
+        f64::INFINITY
+    } else {
+        20.0 * (255.0 / rmse).log10()
+    }
+}
+
+fn write_diff(path: &Path, a: &[u8], b: &[u8]) {
+    // the writer gamma corrects, so hand it linear values that come out as the scaled difference
+    let pixels: Vec<_> = a
+        .chunks(3)
+        .zip(b.chunks(3))
+        .map(|(x, y)| {
+            let channel = |i: usize| {
+                let d = ((x[i] as f64 - y[i] as f64).abs() * DIFF_GAIN / 255.0).min(1.0);
+                (d * d) as f32
+            };
+            crate::vec3::Color::new(channel(0), channel(1), channel(2))
+        })
+        .collect();
+    PngWriter
+        .write(path.to_str().unwrap(), SIZE, SIZE, &pixels)
+        .unwrap();
+}
+
+#[test]
+fn cornell_box() {
+    check_builtin("cornell_box_classic");
+}
+
+#[test]
+fn checkered_spheres() {
+    check_builtin("two_checkered_spheres");
+}
+
+#[test]
+fn perlin_spheres() {
+    check_builtin("two_perlin_spheres");
+}
+
+#[test]
+fn cornell_smoke() {
+    check_builtin("cornell_smoke");
+}
+
+#[test]
+fn avocado() {
+    check_file("avocado");
+}
@@ -0,0 +1,305 @@
+use crate::camera::Camera;
+use crate::film::{Film, FilmTile};
+use crate::hittable::*;
+use crate::integrator::{Integrator, IntegratorKind, Scene};
+use crate::light::Light;
+use crate::light_sampler::LightSamplerKind;
+use crate::progress::Progress;
+use crate::rng;
+use crate::sampler::SamplerKind;
+use crate::spectrum;
+use crate::vec3::*;
+
+use rayon::prelude::*;
+
+use std::cell::Cell;
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    Hilbert,
+    Spiral,
+    Scanline,
+}
+
+impl TileOrder {
+    pub const NAMES: &'static str = "hilbert, spiral, scanline";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "hilbert" => Some(TileOrder::Hilbert),
+            "spiral" => Some(TileOrder::Spiral),
+            "scanline" => Some(TileOrder::Scanline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    // bounces a path can take in total, and of each kind
+    pub max_depth: i32,
+    pub max_diffuse: i32,
+    pub max_specular: i32,
+    pub max_volume: i32,
+    // bounces before Russian roulette can end a path
+    pub rr_depth: i32,
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    pub sampler: SamplerKind,
+    pub integrator: IntegratorKind,
+    pub light_sampler: LightSamplerKind,
+    pub seed: u32,
+    // trace every path at a wavelength of its own, with colours turned into spectra
+    pub spectral: bool,
+    // draw the progress bar on stderr
+    pub progress: bool,
+}
+
+// A square block of the film, in film coordinates (row 0 at the top). Tiles on the right and
+// bottom edges are cut to the image size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x0: usize,
+    pub row0: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Covers the image with tiles, listed in the order they should be handed out.
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let cols = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let mut grid: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|ty| (0..cols).map(move |tx| (tx, ty)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Hilbert => {
+            let n = cols.max(rows).next_power_of_two();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+        TileOrder::Spiral => {
+            // rings around the center tile, walked by angle, so the middle of the image
+            // shows up first
+            let cx = (cols as f32 - 1.0) / 2.0;
+            let cy = (rows as f32 - 1.0) / 2.0;
+            let key = |&(tx, ty): &(usize, usize)| {
+                let dx = tx as f32 - cx;
+                let dy = ty as f32 - cy;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+    }
+
+    grid.into_iter()
+        .map(|(tx, ty)| Tile {
+            x0: tx * size,
+            row0: ty * size,
+            width: size.min(width - tx * size),
+            height: size.min(height - ty * size),
+        })
+        .collect()
+}
+
+// distance along the Hilbert curve filling an n x n grid, n a power of two
+fn hilbert_index(n: usize, x: usize, y: usize) -> usize {
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+thread_local! {
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn count_ray() {
+    RAYS_TRACED.with(|c| c.set(c.get() + 1));
+}
+
+fn take_ray_count() -> u64 {
+    RAYS_TRACED.with(|c| c.replace(0))
+}
+
+pub struct Renderer<'a> {
+    settings: &'a Settings,
+    cam: &'a Camera,
+    scene: Scene<'a>,
+    integrator: Box<dyn Integrator>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(
+        settings: &'a Settings,
+        cam: &'a Camera,
+        background: Color,
+        world: &'a HittableList,
+        lights: &[Arc<dyn Light>],
+    ) -> Self {
+        let scene = Scene::new(world, lights, background, settings.light_sampler);
+        let integrator = settings.integrator.create(settings, &scene);
+        Self {
+            settings,
+            cam,
+            scene,
+            integrator,
+        }
+    }
+
+    pub fn render(&self, label: &str) -> Film {
+        let mut film = Film::new(self.settings.width, self.settings.height);
+        let samples = 0..self.settings.samples_per_pixel;
+        self.render_pass(&mut film, |_, _| samples.clone(), label, |_| {});
+        film
+    }
+
+    // Adds samples to the film, so a render can be split in several passes over the same film.
+    // `samples(x, row)` gives the indices of the samples that pixel gets in this pass. Tiles are
+    // rendered in parallel: workers only touch their own tile and send it back when done, and
+    // the calling thread merges them into the film, keeps the progress bar up to date and calls
+    // `after_tile` after every merge.
+    pub fn render_pass(
+        &self,
+        film: &mut Film,
+        samples: impl Fn(usize, usize) -> Range<usize> + Sync,
+        label: &str,
+        mut after_tile: impl FnMut(&Film),
+    ) {
+        let s = self.settings;
+        let tiles = tiles(s.width, s.height, s.tile_size, s.tile_order);
+        let mut progress = Progress::new(label, (s.width * s.height) as u64);
+        if !s.progress {
+            progress = progress.hidden();
+        }
+
+        let samples = &samples;
+        let mut invalid = vec![];
+        let (tx, rx) = mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                tiles.par_iter().for_each_with(tx, |tx, tile| {
+                    let (rendered, invalid) = self.render_tile(tile, samples);
+                    // the receiver lives until every tile is in
+                    tx.send((rendered, take_ray_count(), invalid)).unwrap();
+                });
+            });
+
+            for (tile, rays, bad) in rx {
+                invalid.extend(bad);
+                film.merge_tile(&tile);
+                progress.advance((tile.width * tile.height) as u64, rays);
+                after_tile(film);
+            }
+        });
+
+        progress.finish();
+        report_invalid(invalid);
+    }
+
+    fn render_tile(
+        &self,
+        tile: &Tile,
+        samples: impl Fn(usize, usize) -> Range<usize>,
+    ) -> (FilmTile, Vec<InvalidSample>) {
+        let s = self.settings;
+        let mut out = FilmTile::new(tile.x0, tile.row0, tile.width, tile.height);
+        let mut invalid = vec![];
+        let mut sampler = s.sampler.create(s.samples_per_pixel, s.seed);
+        let spectral = s.spectral && s.integrator.traces_light();
+
+        for row in tile.row0..tile.row0 + tile.height {
+            // film rows go top to bottom, camera v bottom to top
+            let y = s.height - 1 - row;
+
+            for x in tile.x0..tile.x0 + tile.width {
+                let pixel = out.pixel_mut(x, row);
+                for i in samples(x, row) {
+                    sampler.start_sample(x as u32, y as u32, i as u32);
+                    rng::seed_sample(s.seed, x as u32, y as u32, i as u32);
+                    let (du, dv) = sampler.get_2d();
+                    let u = (x as f32 + du) / (s.width - 1).max(1) as f32;
+                    let v = (y as f32 + dv) / (s.height - 1).max(1) as f32;
+
+                    let r = self.cam.get_ray(u, v, &mut *sampler);
+                    spectrum::start_path(spectral, &mut *sampler);
+                    let radiance = self.integrator.li(r, &self.scene, &mut *sampler);
+                    let lambda = spectrum::take_hero_wavelength();
+                    if radiance.x.is_finite() && radiance.y.is_finite() && radiance.z.is_finite() {
+                        match lambda {
+                            Some(lambda) => pixel.add_spectral_sample(radiance, lambda, 1.0),
+                            None => pixel.add_sample(radiance, 1.0),
+                        }
+                    } else {
+                        // still counted, so the pixel moves on to its next sample
+                        pixel.add_sample(Color::new_empty(), 0.0);
+                        invalid.push(InvalidSample {
+                            x,
+                            row,
+                            index: i,
+                            radiance,
+                        });
+                    }
+                }
+            }
+        }
+
+        (out, invalid)
+    }
+}
+
+// A sample whose radiance came out NaN or infinite. Renders are deterministic, so the pixel and
+// sample index are enough to trace the same path again.
+struct InvalidSample {
+    x: usize,
+    row: usize,
+    index: usize,
+    radiance: Color,
+}
+
+// invalid samples listed in full, the rest are only counted
+const INVALID_REPORTED: usize = 5;
+
+fn report_invalid(mut invalid: Vec<InvalidSample>) {
+    if invalid.is_empty() {
+        return;
+    }
+
+    invalid.sort_by_key(|s| (s.row, s.x, s.index));
+    eprintln!(
+        "warning: dropped {} sample{} with NaN or infinite radiance",
+        invalid.len(),
+        if invalid.len() == 1 { "" } else { "s" }
+    );
+    for s in invalid.iter().take(INVALID_REPORTED) {
+        eprintln!(
+            "    pixel ({}, {}) sample {}: ({}, {}, {})",
+            s.x, s.row, s.index, s.radiance.x, s.radiance.y, s.radiance.z
+        );
+    }
+    if invalid.len() > INVALID_REPORTED {
+        eprintln!("    and {} more", invalid.len() - INVALID_REPORTED);
+    }
+}
@@ -0,0 +1,71 @@
+use rand::distributions::{Distribution, Standard};
+use rand::{Error, RngCore};
+
+use std::cell::Cell;
+
+// Per-thread random number generator for the randomness that doesn't go through a Sampler:
+// scene construction (procedural scenes, Perlin tables) and volume scattering distances. The
+// main thread seeds it before building the scene, and the renderer reseeds it from
+// (seed, pixel, sample) before every sample, so what a sample draws doesn't depend on which
+// thread runs it or what that thread rendered before.
+
+thread_local! {
+    static STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn seed(seed: u64) {
+    STATE.with(|s| s.set(mix(seed)));
+}
+
+pub fn seed_sample(seed: u32, x: u32, y: u32, index: u32) {
+    let pixel = ((x as u64) << 32) | y as u64;
+    STATE.with(|s| s.set(mix(seed as u64 ^ mix(pixel ^ mix(index as u64)))));
+}
+
+// drop-in replacement for rand::thread_rng()
+pub fn local() -> LocalRng {
+    LocalRng
+}
+
+// drop-in replacement for rand::random()
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    Standard.sample(&mut LocalRng)
+}
+
+// SplitMix64 over the thread local state
+pub struct LocalRng;
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        STATE.with(|s| {
+            let state = s.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+            s.set(state);
+            mix(state)
+        })
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,495 @@
+use crate::vec3::*;
+
+use std::f32::consts::PI;
+use std::sync::OnceLock;
+
+// Source of the random numbers of a path. Every decision along the path (film position, lens,
+// time, light and BSDF sampling...) takes the next 1D or 2D dimension, so well distributed
+// sequences can stratify all of them together instead of only the film position.
+//
+// `start_sample` is called before each camera ray with the pixel and the index of the sample in
+// that pixel. All implementations are deterministic functions of (seed, pixel, index,
+// dimension), so images don't depend on the order pixels are rendered in.
+pub trait Sampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub const NAMES: &'static str = "random, stratified, halton, sobol, bluenoise";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Some(SamplerKind::Random),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "bluenoise" | "r2" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerKind::Random => "random",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "bluenoise",
+        }
+    }
+
+    // `samples_per_pixel` is only a hint, used by the stratified sampler to size its strata
+    pub fn create(&self, samples_per_pixel: usize, seed: u32) -> Box<dyn Sampler> {
+        let state = SampleState::new(seed);
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler { state }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                state,
+                spp: samples_per_pixel.max(1) as u32,
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler { state }),
+            SamplerKind::Sobol => Box::new(SobolSampler { state }),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler { state }),
+        }
+    }
+}
+
+// what every sampler keeps track of: the current pixel and sample, and the next dimension
+struct SampleState {
+    seed: u32,
+    pixel: u32,
+    index: u32,
+    dim: u32,
+}
+
+impl SampleState {
+    fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dim: 0,
+        }
+    }
+
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(hash(self.seed ^ x).wrapping_add(y));
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn next_dim(&mut self) -> u32 {
+        self.dim += 1;
+        self.dim - 1
+    }
+
+    // uniform value fixed for this pixel and dimension, e.g. a Cranley-Patterson rotation
+    fn rotation(&self, dim: u32, salt: u32) -> f32 {
+        to_float(hash(
+            hash(self.pixel ^ salt) ^ dim.wrapping_mul(0x9e37_79b9),
+        ))
+    }
+
+    // independent uniform value for this pixel, sample and dimension
+    fn random(&self, dim: u32, salt: u32) -> f32 {
+        to_float(hash(
+            hash(self.pixel ^ salt).wrapping_add(hash(self.index) ^ dim.wrapping_mul(0x9e37_79b9)),
+        ))
+    }
+}
+
+// ---------------------------------------------------------------
+
+pub struct RandomSampler {
+    state: SampleState,
+}
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dim();
+        self.state.random(dim, 0)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ---------------------------------------------------------------
+
+// Jittered strata, `spp` of them per dimension, shuffled independently for every pixel and
+// dimension so dimensions don't correlate. Samples past `spp` (adaptive sampling) start another
+// round with a new shuffle.
+pub struct StratifiedSampler {
+    state: SampleState,
+    spp: u32,
+}
+
+impl StratifiedSampler {
+    fn shuffled(&self, dim: u32, strata: u32) -> u32 {
+        let round = self.state.index / self.spp;
+        let i = self.state.index % self.spp;
+        permute(i, strata, hash(self.state.pixel ^ hash(dim ^ hash(round))))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dim();
+        let stratum = self.shuffled(dim, self.spp);
+        (stratum as f32 + self.state.random(dim, 1)) / self.spp as f32
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dim = self.state.next_dim();
+        self.state.next_dim();
+
+        // as square a grid as spp allows; when spp isn't a square some cells stay empty
+        let nx = (self.spp as f32).sqrt().ceil() as u32;
+        let ny = self.spp.div_ceil(nx);
+        let cell = self.shuffled(dim, nx * ny);
+
+        (
+            ((cell % nx) as f32 + self.state.random(dim, 1)) / nx as f32,
+            ((cell / nx) as f32 + self.state.random(dim + 1, 1)) / ny as f32,
+        )
+    }
+}
+
+// ---------------------------------------------------------------
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// Halton sequence, one prime base per dimension. The digits of every dimension go through a
+// random permutation (keeping 0 in place so the tail of zeros stays zero), which breaks the
+// correlation between the larger bases, and every pixel gets its own Cranley-Patterson
+// rotation. Dimensions past the table fall back to independent random values.
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    fn sample(&self, dim: u32) -> f32 {
+        if dim as usize >= PRIMES.len() {
+            return self.state.random(dim, 2);
+        }
+
+        let base = PRIMES[dim as usize];
+        let perm_seed = hash(self.state.seed ^ hash(dim));
+        let inv_base = 1.0 / base as f64;
+
+        let mut n = self.state.index;
+        let mut inv = inv_base;
+        let mut value = 0.0f64;
+        while n > 0 {
+            let digit = n % base;
+            let digit = if digit == 0 {
+                0
+            } else {
+                1 + permute(digit - 1, base - 1, perm_seed)
+            };
+            value += digit as f64 * inv;
+            inv *= inv_base;
+            n /= base;
+        }
+
+        let rotated = value as f32 + self.state.rotation(dim, 3);
+        wrap(rotated)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dim();
+        self.sample(dim)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ---------------------------------------------------------------
+
+// (degree, coefficients, initial direction numbers) of the Sobol dimensions after the first,
+// from Joe and Kuo's new-joe-kuo-6.21201 table
+const SOBOL_PARAMS: [(u32, u32, &[u32]); 15] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+];
+
+const SOBOL_DIMS: usize = SOBOL_PARAMS.len() + 1;
+
+fn sobol_matrices() -> &'static [[u32; 32]; SOBOL_DIMS] {
+    static MATRICES: OnceLock<[[u32; 32]; SOBOL_DIMS]> = OnceLock::new();
+    MATRICES.get_or_init(|| {
+        let mut v = [[0u32; 32]; SOBOL_DIMS];
+        for (k, d) in v[0].iter_mut().enumerate() {
+            *d = 1 << (31 - k);
+        }
+
+        for (dim, &(s, a, m)) in SOBOL_PARAMS.iter().enumerate() {
+            let v = &mut v[dim + 1];
+            let s = s as usize;
+            for k in 0..32 {
+                v[k] = if k < s {
+                    m[k] << (31 - k)
+                } else {
+                    let mut x = v[k - s] ^ (v[k - s] >> s);
+                    for j in 1..s {
+                        if (a >> (s - 1 - j)) & 1 == 1 {
+                            x ^= v[k - j];
+                        }
+                    }
+                    x
+                };
+            }
+        }
+        v
+    })
+}
+
+// Owen scrambled Sobol sequence with a shuffled index per pixel, following Burley's "Practical
+// Hash-based Owen Scrambling". Dimensions past the table reuse it with another index shuffle,
+// which keeps each block of dimensions well distributed on its own.
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    fn sample(&self, dim: u32) -> f32 {
+        let block = dim / SOBOL_DIMS as u32;
+        let matrix = &sobol_matrices()[dim as usize % SOBOL_DIMS];
+
+        let index = nested_uniform_scramble(self.state.index, hash(self.state.pixel ^ hash(block)));
+        let mut x = 0;
+        let mut n = index;
+        let mut k = 0;
+        while n > 0 {
+            if n & 1 == 1 {
+                x ^= matrix[k];
+            }
+            n >>= 1;
+            k += 1;
+        }
+
+        to_float(nested_uniform_scramble(
+            x,
+            hash(self.state.pixel ^ hash(dim ^ 0x5bd1_e995)),
+        ))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dim();
+        self.sample(dim)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ---------------------------------------------------------------
+
+// Jittered R2 sequence from Martin Roberts, "A simple method to construct isotropic quasirandom
+// blue noise point sequences" (the link in wishlist.txt). Consecutive samples of a pixel are
+// spread like blue noise rather than clumping, and each pixel and dimension gets its own
+// toroidal shift. 1D dimensions use the golden ratio (R1) sequence.
+pub struct BlueNoiseSampler {
+    state: SampleState,
+}
+
+// plastic constant, the 2D generalization of the golden ratio
+const R2_G: f64 = 1.324_717_957_244_746;
+const R1_ALPHA: f64 = 0.618_033_988_749_894_9;
+// jitter of the R2 points, with the constants from the article
+const R2_LAMBDA: f32 = 0.5;
+const R2_DELTA0: f32 = 0.76;
+const R2_I0: f32 = 0.7;
+
+impl Sampler for BlueNoiseSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dim();
+        let n = self.state.index as f64;
+        wrap((n * R1_ALPHA).fract() as f32 + self.state.rotation(dim, 4))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dim = self.state.next_dim();
+        self.state.next_dim();
+
+        let n = self.state.index as f64;
+        let a1 = (n / R2_G).fract() as f32;
+        let a2 = (n / (R2_G * R2_G)).fract() as f32;
+
+        let jitter = R2_LAMBDA * R2_DELTA0 * PI.sqrt() / (4.0 * (n as f32 + 1.0 - R2_I0).sqrt());
+        let j1 = jitter * (self.state.random(dim, 5) - 0.5);
+        let j2 = jitter * (self.state.random(dim + 1, 5) - 0.5);
+
+        (
+            wrap(a1 + j1 + self.state.rotation(dim, 4)),
+            wrap(a2 + j2 + self.state.rotation(dim + 1, 4)),
+        )
+    }
+}
+
+// ---------------------------------------------------------------
+// mapping uniform samples to the shapes materials, lights and the camera need
+
+// uniform on the unit disk, concentric mapping
+pub fn sample_disk(u: (f32, f32)) -> (f32, f32) {
+    let (a, b) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// cosine weighted direction around +z
+pub fn sample_cosine_hemisphere(u: (f32, f32)) -> Vec3 {
+    let (x, y) = sample_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    Vec3::new(x, y, z)
+}
+
+// uniform direction
+pub fn sample_sphere(u: (f32, f32)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// uniform inside the unit ball
+pub fn sample_ball(u: (f32, f32), u_radius: f32) -> Vec3 {
+    sample_sphere(u) * u_radius.cbrt()
+}
+
+// ---------------------------------------------------------------
+
+// keeps a value shifted by a rotation in [0, 1)
+fn wrap(x: f32) -> f32 {
+    let x = x - x.floor();
+    if x >= 1.0 {
+        0.0
+    } else {
+        x
+    }
+}
+
+fn to_float(x: u32) -> f32 {
+    (x >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+// lowbias32 by Chris Wellons
+pub fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+// random permutation of 0..l picked by p, without building it. Kensler, "Correlated
+// Multi-Jittered Sampling"
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % l
+}
+
+// Owen scrambling of the bits of x, Laine and Karras' hash applied to the reversed bits
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
@@ -0,0 +1,1029 @@
+use crate::aarect::*;
+use crate::bvh::BVH;
+use crate::camera::Camera;
+use crate::environment::EnvironmentLight;
+use crate::gltf::GLTF;
+use crate::hittable::*;
+use crate::light::*;
+use crate::material::*;
+use crate::matrix4::Matrix4;
+use crate::spectrum::{Dispersion, Spectrum};
+use crate::sphere::*;
+use crate::texture::*;
+use crate::transforms::*;
+use crate::triangle::Triangle;
+use crate::vec3::*;
+
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Scenes described in TOML. A scene file has a [camera] table, an optional background color,
+// named [textures.*] and [materials.*] tables and a list of [[objects]]:
+//
+//     background = [0.0, 0.0, 0.0]
+//
+//     [camera]
+//     lookfrom = [278.0, 278.0, -800.0]
+//     lookat = [278.0, 278.0, 0.0]
+//     vfov = 40.0
+//
+//     [materials.light]
+//     type = "diffuse_light"
+//     emit = [15.0, 15.0, 15.0]
+//
+//     [[objects]]
+//     type = "rect"
+//     plane = "xz"
+//     a = [213.0, 343.0]
+//     b = [227.0, 332.0]
+//     k = 554.0
+//     material = "light"
+//
+//     [[lights]]
+//     type = "spot"
+//     position = [278.0, 500.0, 278.0]
+//     direction = [0.0, -1.0, 0.0]
+//     intensity = [40000.0, 40000.0, 40000.0]
+//     cone_angle = 30.0
+//
+// Objects with an emissive material are sampled as lights on their own. [[lights]] adds lights
+// that aren't objects: "point" (position, intensity), "spot" (position, direction, intensity,
+// cone_angle and an optional falloff_start, in degrees from the axis), "directional"
+// (direction the light travels, irradiance and an optional angular_diameter in degrees) and
+// "environment" (path of an equirectangular .hdr or 8 bit image, and an optional intensity and
+// rotation around the y axis in degrees). An environment replaces the background, which
+// should then be black. See scenes/ for complete examples.
+//
+// Besides colours, textures can be spectra, for rendering with --spectral: "blackbody"
+// (temperature in kelvin), "illuminant" (name of a CIE standard illuminant: a, d65 or e), both
+// with an optional intensity, the luminance of the light, and "spectrum", measured values at
+// increasing wavelengths in nanometers. Without --spectral they are the colour they average
+// out to.
+//
+//     [textures.tungsten]
+//     type = "blackbody"
+//     temperature = 2700.0
+//     intensity = 15.0
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl SceneError {
+    fn new(line: Option<usize>, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    camera: CameraDesc,
+    #[serde(default)]
+    background: [f32; 3],
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: [f32; 3],
+    lookat: [f32; 3],
+    #[serde(default = "default_vup")]
+    vup: [f32; 3],
+    vfov: f32,
+    #[serde(default)]
+    aperture: f32,
+    #[serde(default = "default_focus_dist")]
+    focus_dist: f32,
+    #[serde(default)]
+    time0: f32,
+    #[serde(default = "default_time1")]
+    time1: f32,
+}
+
+fn default_vup() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_focus_dist() -> f32 {
+    10.0
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_tint() -> TextureRef {
+    TextureRef::Color([1.0, 1.0, 1.0])
+}
+
+fn default_time1() -> f32 {
+    1.0
+}
+
+// either an inline color or the name of a texture
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f32; 3]),
+    Name(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f32; 3],
+    },
+    Checker {
+        odd: TextureRef,
+        even: TextureRef,
+    },
+    Noise {
+        scale: f32,
+    },
+    Image {
+        path: String,
+    },
+    PolkaDot {
+        background: TextureRef,
+        foreground: TextureRef,
+        radius: f32,
+        distance: f32,
+    },
+    Blackbody {
+        temperature: f32,
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+    },
+    Illuminant {
+        name: String,
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+    },
+    Spectrum {
+        wavelengths: Vec<f32>,
+        values: Vec<f32>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    OrenNayar {
+        albedo: TextureRef,
+        sigma: f32,
+    },
+    DisneyDiffuse {
+        base_color: TextureRef,
+        roughness: f32,
+    },
+    Metal {
+        albedo: [f32; 3],
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Dielectric {
+        ir: Option<f32>,
+        absorption: Option<[f32; 3]>,
+        attenuation_color: Option<[f32; 3]>,
+        attenuation_distance: Option<f32>,
+        abbe: Option<f32>,
+        cauchy: Option<[f32; 2]>,
+        sellmeier_b: Option<[f32; 3]>,
+        sellmeier_c: Option<[f32; 3]>,
+    },
+    RoughDielectric {
+        ir: f32,
+        roughness: f32,
+        #[serde(default = "default_tint")]
+        tint: TextureRef,
+        absorption: Option<[f32; 3]>,
+        attenuation_color: Option<[f32; 3]>,
+        attenuation_distance: Option<f32>,
+    },
+    ThinDielectric {
+        ir: f32,
+        #[serde(default = "default_tint")]
+        tint: TextureRef,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+    },
+    Isotropic {
+        albedo: TextureRef,
+    },
+    MetallicRoughness {
+        base_color: TextureRef,
+        metallic: f32,
+        roughness: f32,
+    },
+}
+
+#[derive(Deserialize)]
+struct ObjectDesc {
+    #[serde(flatten)]
+    shape: ShapeDesc,
+    #[serde(default)]
+    transforms: Vec<TransformDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ShapeDesc {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        material: String,
+    },
+    MovingSphere {
+        center0: [f32; 3],
+        center1: [f32; 3],
+        #[serde(default)]
+        time0: f32,
+        #[serde(default = "default_time1")]
+        time1: f32,
+        radius: f32,
+        material: String,
+    },
+    Rect {
+        plane: PlaneDesc,
+        a: [f32; 2],
+        b: [f32; 2],
+        k: f32,
+        material: String,
+    },
+    #[serde(rename = "box")]
+    RectBox {
+        min: [f32; 3],
+        max: [f32; 3],
+        material: String,
+    },
+    Triangle {
+        v0: [f32; 3],
+        v1: [f32; 3],
+        v2: [f32; 3],
+        material: String,
+    },
+    ConstantMedium {
+        boundary: Box<ObjectDesc>,
+        density: f32,
+        albedo: TextureRef,
+    },
+    Gltf {
+        path: String,
+        material: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: [f32; 3],
+        intensity: [f32; 3],
+    },
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        intensity: [f32; 3],
+        cone_angle: f32,
+        falloff_start: Option<f32>,
+    },
+    Directional {
+        direction: [f32; 3],
+        irradiance: [f32; 3],
+        #[serde(default)]
+        angular_diameter: f32,
+    },
+    Environment {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+        #[serde(default)]
+        rotation: f32,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum PlaneDesc {
+    Xy,
+    Xz,
+    Yz,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum AxisDesc {
+    X,
+    Y,
+    Z,
+}
+
+// applied in the order they're listed, e.g. transforms = [{ rotate = { axis = "y", angle = 15.0 } },
+// { translate = [265.0, 0.0, 295.0] }]
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate([f32; 3]),
+    Rotate { axis: AxisDesc, angle: f32 },
+    Scale([f32; 3]),
+    // world to object space matrix, as taken by transforms::Transform
+    Matrix([[f32; 4]; 4]),
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+type SceneResult<T> = Result<T, SceneError>;
+
+// Absorption inside a dielectric, given as it is or as the colour white light takes on after
+// attenuation_distance through it, the way glTF has it. Clear when neither is given.
+fn absorption(
+    absorption: Option<[f32; 3]>,
+    color: Option<[f32; 3]>,
+    distance: Option<f32>,
+    line: Option<usize>,
+) -> SceneResult<Color> {
+    match (absorption, color, distance) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(SceneError::new(
+            line,
+            "give either absorption or attenuation_color, not both".to_string(),
+        )),
+        (None, None, Some(_)) => Err(SceneError::new(
+            line,
+            "attenuation_distance needs an attenuation_color".to_string(),
+        )),
+        (_, _, Some(d)) if d <= 0.0 => Err(SceneError::new(
+            line,
+            format!("attenuation_distance must be positive, got {}", d),
+        )),
+        (Some(a), None, None) => Ok(vec3(a)),
+        (None, Some(c), d) => Ok(absorption_at_distance(vec3(c), d.unwrap_or(1.0))),
+        (None, None, None) => Ok(Color::new_empty()),
+    }
+}
+
+// How a dielectric's index of refraction changes with the wavelength, if it does: `ir` at the
+// d line and an Abbe number, or the coefficients of Cauchy's or Sellmeier's equation, which
+// stand in for `ir`.
+fn dispersion(
+    ir: Option<f32>,
+    abbe: Option<f32>,
+    cauchy: Option<[f32; 2]>,
+    sellmeier_b: Option<[f32; 3]>,
+    sellmeier_c: Option<[f32; 3]>,
+    line: Option<usize>,
+) -> SceneResult<Option<Dispersion>> {
+    let err = |msg: &str| Err(SceneError::new(line, msg.to_string()));
+    let sellmeier = match (sellmeier_b, sellmeier_c) {
+        (Some(b), Some(c)) => Some(Dispersion::Sellmeier { b, c }),
+        (None, None) => None,
+        _ => return err("sellmeier_b and sellmeier_c go together"),
+    };
+    let cauchy = cauchy.map(|[a, b]| Dispersion::Cauchy { a, b });
+
+    match (ir, abbe, cauchy, sellmeier) {
+        (_, _, Some(_), Some(_)) => err("give either cauchy or sellmeier coefficients, not both"),
+        (Some(_), _, Some(_), _) | (Some(_), _, _, Some(_)) => {
+            err("cauchy and sellmeier coefficients set the ir themselves, leave it out")
+        }
+        (_, Some(_), Some(_), _) | (_, Some(_), _, Some(_)) => {
+            err("give either an abbe number or cauchy or sellmeier coefficients, not both")
+        }
+        (None, Some(_), None, None) => err("abbe needs an ir"),
+        (Some(_), Some(abbe), None, None) if abbe <= 0.0 => Err(SceneError::new(
+            line,
+            format!("abbe must be positive, got {}", abbe),
+        )),
+        (Some(ir), Some(abbe), None, None) => Ok(Some(Dispersion::from_abbe(ir, abbe))),
+        (None, None, cauchy, sellmeier) => Ok(cauchy.or(sellmeier)),
+        (Some(_), None, None, None) => Ok(None),
+    }
+}
+
+struct Loader<'a> {
+    source: &'a str,
+    base_dir: PathBuf,
+    texture_descs: &'a HashMap<String, TextureDesc>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+}
+
+pub fn load(
+    path: &str,
+    aspect_ratio: f32,
+) -> Result<(Vec<HittableList>, Camera, Color, Vec<LightList>), SceneError> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| SceneError::new(None, format!("can't read '{}': {}", path, e)))?;
+    let base_dir = Path::new(path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    from_str(&source, base_dir, aspect_ratio)
+}
+
+pub fn from_str(
+    source: &str,
+    base_dir: PathBuf,
+    aspect_ratio: f32,
+) -> Result<(Vec<HittableList>, Camera, Color, Vec<LightList>), SceneError> {
+    let desc: SceneDesc = toml::from_str(source).map_err(|e| {
+        let line = e.line_col().map(|(line, _)| line + 1);
+        // toml appends its own "at line x column y", which we already report
+        let mut message = e.to_string();
+        if let Some(i) = message.rfind(" at line ") {
+            message.truncate(i);
+        }
+        SceneError::new(line, message)
+    })?;
+
+    let mut loader = Loader {
+        source,
+        base_dir,
+        texture_descs: &desc.textures,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+    };
+
+    // sorted so errors are reported in a stable order
+    let mut texture_names: Vec<&String> = desc.textures.keys().collect();
+    texture_names.sort();
+    for name in texture_names {
+        loader.texture(name, &mut vec![])?;
+    }
+
+    let mut material_names: Vec<&String> = desc.materials.keys().collect();
+    material_names.sort();
+    for name in material_names {
+        let material = loader.material(name, &desc.materials[name])?;
+        loader.materials.insert(name.clone(), material);
+    }
+
+    let mut world = HittableList::new();
+    for (i, object) in desc.objects.iter().enumerate() {
+        let line = loader.header_line("[[objects]]", i);
+        world.push_arc(loader.object(object, line)?);
+    }
+
+    let mut lights = vec![];
+    for (i, light) in desc.lights.iter().enumerate() {
+        let line = loader.header_line("[[lights]]", i);
+        lights.push(loader.light(light, line)?);
+    }
+
+    let cam = &desc.camera;
+    let cam = Camera::new(
+        vec3(cam.lookfrom),
+        vec3(cam.lookat),
+        vec3(cam.vup),
+        cam.vfov,
+        aspect_ratio,
+        cam.aperture,
+        cam.focus_dist,
+        cam.time0,
+        cam.time1,
+    );
+
+    Ok((vec![world], cam, vec3(desc.background), vec![lights]))
+}
+
+impl Loader<'_> {
+    // line number of the nth occurrence of a table header, so errors found after parsing
+    // still point somewhere useful
+    fn header_line(&self, header: &str, nth: usize) -> Option<usize> {
+        self.source
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| l.trim_start().starts_with(header))
+            .nth(nth)
+            .map(|(i, _)| i + 1)
+    }
+
+    fn resolve_path(&self, path: &str) -> String {
+        self.base_dir.join(path).to_string_lossy().into_owned()
+    }
+
+    fn texture(&mut self, name: &str, stack: &mut Vec<String>) -> SceneResult<Arc<dyn Texture>> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+
+        let line = self.header_line(&format!("[textures.{}]", name), 0);
+        let desc = self
+            .texture_descs
+            .get(name)
+            .ok_or_else(|| SceneError::new(None, format!("unknown texture '{}'", name)))?;
+
+        if stack.iter().any(|n| n == name) {
+            return Err(SceneError::new(
+                line,
+                format!("texture '{}' references itself", name),
+            ));
+        }
+        stack.push(name.to_string());
+
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColorTexture::new(vec3(*color))),
+            TextureDesc::Checker { odd, even } => {
+                let odd = self.texture_ref(odd, line, stack)?;
+                let even = self.texture_ref(even, line, stack)?;
+                Arc::new(CheckerTexture::new(Box::new(odd), Box::new(even)))
+            }
+            TextureDesc::Noise { scale } => Arc::new(NoiseTexture::new(*scale)),
+            TextureDesc::Blackbody {
+                temperature,
+                intensity,
+            } => {
+                if *temperature <= 0.0 {
+                    return Err(SceneError::new(
+                        line,
+                        format!("temperature must be positive, got {}", temperature),
+                    ));
+                }
+                Arc::new(SpectrumTexture::illuminant(
+                    Spectrum::Blackbody(*temperature),
+                    *intensity,
+                ))
+            }
+            TextureDesc::Illuminant { name, intensity } => {
+                let spectrum = Spectrum::illuminant(name).ok_or_else(|| {
+                    SceneError::new(
+                        line,
+                        format!(
+                            "unknown illuminant '{}' (supported: {})",
+                            name,
+                            Spectrum::ILLUMINANT_NAMES
+                        ),
+                    )
+                })?;
+                Arc::new(SpectrumTexture::illuminant(spectrum, *intensity))
+            }
+            TextureDesc::Spectrum {
+                wavelengths,
+                values,
+            } => {
+                if wavelengths.is_empty() || wavelengths.len() != values.len() {
+                    return Err(SceneError::new(
+                        line,
+                        "a spectrum needs as many values as wavelengths, at least one".to_string(),
+                    ));
+                }
+                if wavelengths.windows(2).any(|w| w[0] >= w[1]) {
+                    return Err(SceneError::new(
+                        line,
+                        "spectrum wavelengths must increase".to_string(),
+                    ));
+                }
+                let spectrum = Spectrum::Sampled {
+                    wavelengths: wavelengths.clone(),
+                    values: values.clone(),
+                };
+                Arc::new(SpectrumTexture::new(spectrum, 1.0))
+            }
+            TextureDesc::Image { path } => {
+                let path = self.resolve_path(path);
+                let image = ImageTexture::open(&path).map_err(|e| {
+                    SceneError::new(line, format!("can't load image '{}': {}", path, e))
+                })?;
+                Arc::new(image)
+            }
+            TextureDesc::PolkaDot {
+                background,
+                foreground,
+                radius,
+                distance,
+            } => {
+                let bg = self.texture_ref(background, line, stack)?;
+                let fg = self.texture_ref(foreground, line, stack)?;
+                Arc::new(PolkaDotTexture::new(
+                    Box::new(bg),
+                    Box::new(fg),
+                    *radius,
+                    *distance,
+                ))
+            }
+        };
+
+        stack.pop();
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn texture_ref(
+        &mut self,
+        texture: &TextureRef,
+        line: Option<usize>,
+        stack: &mut Vec<String>,
+    ) -> SceneResult<Arc<dyn Texture>> {
+        match texture {
+            TextureRef::Color(c) => Ok(Arc::new(SolidColorTexture::new(vec3(*c)))),
+            TextureRef::Name(name) => {
+                if !self.texture_descs.contains_key(name) {
+                    return Err(SceneError::new(line, format!("unknown texture '{}'", name)));
+                }
+                self.texture(name, stack)
+            }
+        }
+    }
+
+    fn material(&mut self, name: &str, desc: &MaterialDesc) -> SceneResult<Arc<dyn Material>> {
+        let line = self.header_line(&format!("[materials.{}]", name), 0);
+
+        let material: Arc<dyn Material> = match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                Arc::new(Lambertian::new(self.texture_ref(albedo, line, &mut vec![])?))
+            }
+            MaterialDesc::OrenNayar { albedo, sigma } => Arc::new(OrenNayar::new(
+                self.texture_ref(albedo, line, &mut vec![])?,
+                *sigma,
+            )),
+            MaterialDesc::DisneyDiffuse {
+                base_color,
+                roughness,
+            } => Arc::new(DisneyDiffuse::new(
+                self.texture_ref(base_color, line, &mut vec![])?,
+                *roughness,
+            )),
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::new(vec3(*albedo), *fuzz)),
+            MaterialDesc::Dielectric {
+                ir,
+                absorption: a,
+                attenuation_color,
+                attenuation_distance,
+                abbe,
+                cauchy,
+                sellmeier_b,
+                sellmeier_c,
+            } => {
+                let a = absorption(*a, *attenuation_color, *attenuation_distance, line)?;
+                let dispersion = dispersion(*ir, *abbe, *cauchy, *sellmeier_b, *sellmeier_c, line)?;
+                match (ir, dispersion) {
+                    (_, Some(dispersion)) => {
+                        Arc::new(Dieletric::with_absorption(1.0, a).with_dispersion(dispersion))
+                    }
+                    (Some(ir), None) => Arc::new(Dieletric::with_absorption(*ir, a)),
+                    (None, None) => {
+                        return Err(SceneError::new(
+                            line,
+                            "dielectric needs an ir, or cauchy or sellmeier coefficients"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+            MaterialDesc::RoughDielectric {
+                ir,
+                roughness,
+                tint,
+                absorption: a,
+                attenuation_color,
+                attenuation_distance,
+            } => Arc::new(RoughDielectric::new(
+                *ir,
+                *roughness,
+                self.texture_ref(tint, line, &mut vec![])?,
+                absorption(*a, *attenuation_color, *attenuation_distance, line)?,
+            )),
+            MaterialDesc::ThinDielectric { ir, tint } => Arc::new(ThinDielectric::new(
+                *ir,
+                self.texture_ref(tint, line, &mut vec![])?,
+            )),
+            MaterialDesc::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new(self.texture_ref(emit, line, &mut vec![])?))
+            }
+            MaterialDesc::Isotropic { albedo } => Arc::new(Isotropic::new(Box::new(
+                self.texture_ref(albedo, line, &mut vec![])?,
+            ))),
+            MaterialDesc::MetallicRoughness {
+                base_color,
+                metallic,
+                roughness,
+            } => Arc::new(MetallicRoughness::new(
+                self.texture_ref(base_color, line, &mut vec![])?,
+                *metallic,
+                *roughness,
+            )),
+        };
+
+        Ok(material)
+    }
+
+    fn material_ref(&self, name: &str, line: Option<usize>) -> SceneResult<Arc<dyn Material>> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SceneError::new(line, format!("unknown material '{}'", name)))
+    }
+
+    fn object(&mut self, desc: &ObjectDesc, line: Option<usize>) -> SceneResult<Arc<dyn Hittable>> {
+        let mut object: Arc<dyn Hittable> = match &desc.shape {
+            ShapeDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => Arc::new(Sphere::new(
+                vec3(*center),
+                *radius,
+                self.material_ref(material, line)?,
+            )),
+            ShapeDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Arc::new(MovingSphere::new(
+                vec3(*center0),
+                vec3(*center1),
+                *time0,
+                *time1,
+                *radius,
+                self.material_ref(material, line)?,
+            )),
+            ShapeDesc::Rect {
+                plane,
+                a,
+                b,
+                k,
+                material,
+            } => {
+                let plane = match plane {
+                    PlaneDesc::Xy => Plane::XY,
+                    PlaneDesc::Xz => Plane::XZ,
+                    PlaneDesc::Yz => Plane::YZ,
+                };
+                Arc::new(AARect::new(
+                    plane,
+                    self.material_ref(material, line)?,
+                    a[0],
+                    a[1],
+                    b[0],
+                    b[1],
+                    *k,
+                ))
+            }
+            ShapeDesc::RectBox { min, max, material } => Arc::new(RectBox::new(
+                vec3(*min),
+                vec3(*max),
+                self.material_ref(material, line)?,
+            )),
+            ShapeDesc::Triangle {
+                v0,
+                v1,
+                v2,
+                material,
+            } => Arc::new(Triangle::new(
+                self.material_ref(material, line)?,
+                vec3(*v0),
+                vec3(*v1),
+                vec3(*v2),
+            )),
+            ShapeDesc::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                let boundary = self.object(boundary, line)?;
+                let albedo = self.texture_ref(albedo, line, &mut vec![])?;
+                Arc::new(ConstantMedium::new(boundary, *density, albedo))
+            }
+            ShapeDesc::Gltf { path, material } => {
+                let material = match material {
+                    Some(name) => Some(self.material_ref(name, line)?),
+                    None => None,
+                };
+                self.gltf(path, material, line)?
+            }
+        };
+
+        for transform in &desc.transforms {
+            object = match *transform {
+                TransformDesc::Translate(offset) => Arc::new(Translate::new(object, vec3(offset))),
+                TransformDesc::Rotate { axis, angle } => {
+                    let axis = match axis {
+                        AxisDesc::X => Axis::X,
+                        AxisDesc::Y => Axis::Y,
+                        AxisDesc::Z => Axis::Z,
+                    };
+                    Arc::new(Rotate::new(object, axis, angle))
+                }
+                TransformDesc::Scale(factors) => {
+                    if factors.iter().any(|f| *f <= 0.0) {
+                        return Err(SceneError::new(
+                            line,
+                            "scale factors must be positive".to_string(),
+                        ));
+                    }
+                    Arc::new(Scale::new(object, vec3(factors)))
+                }
+                TransformDesc::Matrix(m) => {
+                    if Matrix4(m).inverse().is_none() {
+                        return Err(SceneError::new(
+                            line,
+                            "transform matrix is not invertible".to_string(),
+                        ));
+                    }
+                    Arc::new(Transform::new(object, Matrix4(m)))
+                }
+            };
+        }
+
+        Ok(object)
+    }
+
+    fn gltf(
+        &self,
+        path: &str,
+        material: Option<Arc<dyn Material>>,
+        line: Option<usize>,
+    ) -> SceneResult<Arc<dyn Hittable>> {
+        let path = self.resolve_path(path);
+        let gltf = GLTF::new(path.clone())
+            .map_err(|e| SceneError::new(line, format!("can't load glTF '{}': {}", path, e)))?;
+
+        let default_material: Arc<dyn Material> =
+            Arc::new(Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73))));
+        let gltf_materials: Vec<Arc<dyn Material>> = gltf
+            .materials
+            .iter()
+            .map(|m| m.material())
+            .collect();
+
+        let mut triangles: Vec<Arc<dyn Hittable>> = Vec::new();
+        for mesh in &gltf.meshes {
+            let material = material.clone().unwrap_or_else(|| {
+                gltf_materials
+                    .get(mesh.mat_index)
+                    .cloned()
+                    .unwrap_or_else(|| default_material.clone())
+            });
+
+            let indices: Vec<u32> = if mesh.indices.is_empty() {
+                (0..mesh.positions.len() as u32).collect()
+            } else {
+                mesh.indices.clone()
+            };
+
+            for tri in indices.chunks_exact(3) {
+                let v = |i: u32| mesh.transform * mesh.positions[i as usize];
+                triangles.push(Arc::new(Triangle::new(
+                    material.clone(),
+                    v(tri[0]),
+                    v(tri[1]),
+                    v(tri[2]),
+                )));
+            }
+        }
+
+        if triangles.is_empty() {
+            return Err(SceneError::new(
+                line,
+                format!("glTF '{}' has no triangles", path),
+            ));
+        }
+
+        Ok(Arc::new(BVH::new(triangles, 0.0, 1.0)))
+    }
+
+    fn light(&self, desc: &LightDesc, line: Option<usize>) -> SceneResult<Arc<dyn Light>> {
+        let direction = |d: [f32; 3]| {
+            let d = vec3(d);
+            if d.near_zero() {
+                Err(SceneError::new(
+                    line,
+                    "light direction can't be zero".to_string(),
+                ))
+            } else {
+                Ok(d)
+            }
+        };
+
+        Ok(match *desc {
+            LightDesc::Point {
+                position,
+                intensity,
+            } => Arc::new(PointLight::new(vec3(position), vec3(intensity))),
+            LightDesc::Spot {
+                position,
+                direction: d,
+                intensity,
+                cone_angle,
+                falloff_start,
+            } => {
+                if !(cone_angle > 0.0 && cone_angle <= 180.0) {
+                    return Err(SceneError::new(
+                        line,
+                        format!("cone_angle must be in (0, 180], got {}", cone_angle),
+                    ));
+                }
+                Arc::new(SpotLight::new(
+                    vec3(position),
+                    direction(d)?,
+                    vec3(intensity),
+                    cone_angle,
+                    falloff_start.unwrap_or(cone_angle),
+                ))
+            }
+            LightDesc::Directional {
+                direction: d,
+                irradiance,
+                angular_diameter,
+            } => Arc::new(DirectionalLight::new(
+                direction(d)?,
+                vec3(irradiance),
+                angular_diameter,
+            )),
+            LightDesc::Environment {
+                ref path,
+                intensity,
+                rotation,
+            } => {
+                let path = self.resolve_path(path);
+                let light = EnvironmentLight::open(&path, intensity, rotation).map_err(|e| {
+                    SceneError::new(line, format!("can't load environment '{}': {}", path, e))
+                })?;
+                Arc::new(light)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str =
+        "[camera]\nlookfrom = [0.0, 0.0, 1.0]\nlookat = [0.0, 0.0, 0.0]\nvfov = 40.0\n";
+
+    // the error loading `objects` after a camera, which takes up the first four lines
+    fn error(objects: &str) -> SceneError {
+        let source = format!("{}{}", CAMERA, objects);
+        match from_str(&source, PathBuf::new(), 1.0) {
+            Ok(_) => panic!("loaded a scene that should have been refused:\n{}", source),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn unknown_material_reports_object_line() {
+        let e = error(
+            "\n[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+             material = \"missing\"\n",
+        );
+        assert_eq!(e.line, Some(6), "{}", e);
+        assert!(e.to_string().starts_with("line 6: "), "{}", e);
+        assert!(e.message.contains("unknown material 'missing'"), "{}", e);
+    }
+
+    // toml points at the table the key is in
+    #[test]
+    fn unknown_key_reports_its_table() {
+        let e = error(
+            "\n[materials.grey]\ntype = \"lambertian\"\nalbedo = [0.5, 0.5, 0.5]\n\
+             shininess = 2.0\n",
+        );
+        assert_eq!(e.line, Some(6), "{}", e);
+        assert!(e.message.contains("unknown field `shininess`"), "{}", e);
+    }
+
+    #[test]
+    fn missing_field_reports_its_table() {
+        let e = error(
+            "\n[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nmaterial = \"grey\"\n",
+        );
+        assert_eq!(e.line, Some(6), "{}", e);
+        assert!(e.message.contains("missing field `radius`"), "{}", e);
+    }
+}
@@ -3,8 +3,10 @@ use crate::bvh::*;
 use crate::camera::*;
 use crate::gltf::GLTF;
 use crate::hittable::*;
+use crate::light::LightList;
 use crate::material::*;
 use crate::matrix4::Matrix4;
+use crate::rng;
 use crate::sphere::*;
 use crate::texture::*;
 use crate::transforms::*;
@@ -14,15 +16,41 @@ use crate::vec3::*;
 use std::sync::Arc;
 use rand::Rng;
 
-pub fn cornell_box(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<HittableList>) {
+pub type Scene = (Vec<HittableList>, Camera, Color, Vec<LightList>);
+
+// scenes that load assets from disk report a missing file instead of panicking
+pub type SceneFn = fn(f32) -> Result<Scene, String>;
+
+// every scene selectable from the command line, by function name
+const SCENES: &[(&str, SceneFn)] = &[
+    ("cornell_box", cornell_box),
+    ("book2_scene", |a| Ok(book2_scene(a))),
+    ("cornell_box_animated", |a| Ok(cornell_box_animated(a))),
+    ("simple_light", |a| Ok(simple_light(a))),
+    ("first_scene", |a| Ok(first_scene(a))),
+    ("cornell_box_classic", |a| Ok(cornell_box_classic(a))),
+    ("two_checkered_spheres", |a| Ok(two_checkered_spheres(a))),
+    ("two_perlin_spheres", |a| Ok(two_perlin_spheres(a))),
+    ("cornell_smoke", |a| Ok(cornell_smoke(a))),
+];
+
+pub fn by_name(name: &str) -> Option<SceneFn> {
+    SCENES.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+}
+
+pub fn names() -> Vec<&'static str> {
+    SCENES.iter().map(|(n, _)| *n).collect()
+}
+
+pub fn cornell_box(aspect_ratio: f32) -> Result<Scene, String> {
     let background = Color::new(0.0, 0.0, 0.0);
     let mut world_vec = vec![];
     let mut lights_vec = vec![];
 
-    let gltf = GLTF::new("../models/matilda/scene.gltf".to_string()).unwrap();
+    let path = "../models/matilda/scene.gltf";
+    let gltf = GLTF::new(path.to_string()).map_err(|e| format!("{}: {}", path, e))?;
 
     let mut world = HittableList::new();
-    let mut lights = HittableList::new();
 
     let red = Lambertian::new(SolidColorTexture::new(Color::new(0.65, 0.05, 0.05)));
     let white = Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73)));
@@ -31,28 +59,15 @@ pub fn cornell_box(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<
 
     let mut gltf_import: Vec<Arc<dyn Hittable>> = Vec::new();
 
-    eprintln!("materials: {:?}", gltf.materials);
     for mesh in gltf.meshes {
         for indices in mesh.indices.chunks(3) {
             let gltf_mat = &gltf.materials[mesh.mat_index];
-            let albedo = gltf_mat.albedo;
-            eprintln!("{:?}", mesh.transform);
-
-            // let (albedo, roughness) = &gltf_mat.metallic_roughness();
-
-            /*
-            let mat: Material = if gltf_mat.metallic == 0.0 {
-                Lambertian::new(SolidColorTexture::new(albedo))
-            } else {
-                Metal::new(albedo, roughness)
-            };*/
-
             gltf_import.push(Arc::new(Translate::new(
                 Rotate::new(
                 Rotate::new(
                 Rotate::new(
                     Triangle::new(
-                        Lambertian::new(SolidColorTexture::new(albedo)),
+                        gltf_mat.material(),
                         Matrix4::scale(Vec3::new(100.0, 100.0, 100.0))
                             * mesh.transform
                             * mesh.positions[indices[0] as usize],
@@ -81,10 +96,8 @@ pub fn cornell_box(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<
     world.push(BVH::new(gltf_import, 0.0, 1.0));
 
     let light = DiffuseLight::new(SolidColorTexture::new(Color::new(12.0, 6.807, 2.086)));
-    let light_ceiling = AARect::new(Plane::XZ, light.clone(), 177.0, 392.0, 163.0, 393.0, 554.0);
+    let light_ceiling = AARect::new(Plane::XZ, light, 177.0, 392.0, 163.0, 393.0, 554.0);
     world.push(light_ceiling);
-    let light_ceiling = AARect::new(Plane::XZ, light.clone(), 177.0, 392.0, 163.0, 393.0, 554.0);
-    lights.push(light_ceiling);
 
     world.push(AARect::new(
         Plane::YZ,
@@ -158,7 +171,7 @@ pub fn cornell_box(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<
     world.push(box2);
 
     world_vec.push(world);
-    lights_vec.push(lights);
+    lights_vec.push(vec![]);
 
     let lookfrom = Point3::new(278.0, 278.0, -800.0);
     let lookat = Point3::new(278.0, 278.0, 0.0);
@@ -179,13 +192,12 @@ pub fn cornell_box(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<
         1.0,
     );
 
-    (world_vec, cam, background, lights_vec)
+    Ok((world_vec, cam, background, lights_vec))
 }
 
 pub fn book2_scene(
     aspect_ratio: f32
-) -> (Vec<HittableList>, Camera, Color, Vec<HittableList>) {
-    let mut lights = HittableList::new();
+) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
     let mut objects = HittableList::new();
 
     let ground = Lambertian::new(SolidColorTexture::new(Vec3::new(0.48, 0.83, 0.53)));
@@ -200,7 +212,7 @@ pub fn book2_scene(
             let z0 = -1000.0 + j as f32 * w;
             let y0 = 0.0;
             let x1 = x0 + w;
-            let y1 = rand::random::<f32>() * 100.0;
+            let y1 = rng::random::<f32>() * 100.0;
             let z1 = z0 + w;
             boxes1.push(Arc::new(RectBox::new(Point3::new(x0, y0, z0), Point3::new(x1, y1, z1), ground.clone())));
         }
@@ -210,8 +222,7 @@ pub fn book2_scene(
 
 
     let light = DiffuseLight::new(SolidColorTexture::new(Color::new(7.0, 7.0, 7.0)));
-    objects.push(AARect::new(Plane::XZ, light.clone(), 123.0, 423.0, 147.0, 412.0, 554.0));
-    lights.push(AARect::new(Plane::XZ, light.clone(), 123.0, 423.0, 147.0, 412.0, 554.0));
+    objects.push(AARect::new(Plane::XZ, light, 123.0, 423.0, 147.0, 412.0, 554.0));
 
     let center1 = Point3::new(400.0, 400.0, 200.0);
     let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
@@ -249,20 +260,18 @@ pub fn book2_scene(
         0.0,
         1.0,
     );
-    (vec!(objects), cam, background, vec!(lights))
+    (vec!(objects), cam, background, vec![vec![]])
 }
 
 pub fn cornell_box_animated(
     aspect_ratio: f32,
-) -> (Vec<HittableList>, Camera, Color, Vec<HittableList>) {
+) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
     let background = Color::new(0.0, 0.0, 0.0);
     let mut world_vec = vec![];
     let mut lights_vec = vec![];
 
     for i in 0..15 {
         let mut world = HittableList::new();
-        let mut lights = HittableList::new();
-
         let red: Lambertian<SolidColorTexture> =
             Lambertian::new(SolidColorTexture::new(Color::new(0.65, 0.05, 0.05)));
         let white = Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73)));
@@ -270,12 +279,8 @@ pub fn cornell_box_animated(
         let aluminum = Metal::new(Color::new(0.8, 0.85, 0.88), 0.0);
 
         let light = DiffuseLight::new(SolidColorTexture::new(Color::new(12.0, 6.807, 2.086)));
-        let light_ceiling =
-            AARect::new(Plane::XZ, light.clone(), 177.0, 392.0, 163.0, 393.0, 554.0);
+        let light_ceiling = AARect::new(Plane::XZ, light, 177.0, 392.0, 163.0, 393.0, 554.0);
         world.push(light_ceiling);
-        let light_ceiling =
-            AARect::new(Plane::XZ, light.clone(), 177.0, 392.0, 163.0, 393.0, 554.0);
-        lights.push(light_ceiling);
 
         world.push(AARect::new(Plane::YZ, green, 0.0, 555.0, 0.0, 555.0, 555.0));
         world.push(AARect::new(Plane::YZ, red, 0.0, 555.0, 0.0, 555.0, 0.0));
@@ -331,7 +336,7 @@ pub fn cornell_box_animated(
         world.push(box2);
 
         world_vec.push(world);
-        lights_vec.push(lights);
+        lights_vec.push(vec![]);
     }
 
     let lookfrom = Point3::new(278.0, 278.0, -800.0);
@@ -356,9 +361,8 @@ pub fn cornell_box_animated(
     (world_vec, cam, background, lights_vec)
 }
 
-pub fn simple_light(aspect_ratio: f32) -> (HittableList, Camera, Color, HittableList) {
+pub fn simple_light(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
     let mut world = HittableList::new();
-    let mut lights = HittableList::new();
     let background = Color::new_empty();
 
     let pertext = NoiseTexture::new(4.0);
@@ -378,22 +382,20 @@ pub fn simple_light(aspect_ratio: f32) -> (HittableList, Camera, Color, Hittable
     let difflight = DiffuseLight::new(SolidColorTexture::new(Color::new(4.0, 7.0, 9.0)));
     world.push(AARect::new(
         Plane::XY,
-        difflight.clone(),
+        difflight,
         3.0,
         5.0,
         1.0,
         3.0,
         -2.0,
     ));
-    lights.push(AARect::new(Plane::XY, difflight, 3.0, 5.0, 1.0, 3.0, -2.0));
 
     let difflight = DiffuseLight::new(SolidColorTexture::new(Color::new(9.0, 3.0, 2.0)));
     world.push(Sphere::new(
         Point3::new(1.0, 5.0, 4.0),
         1.0,
-        difflight.clone(),
+        difflight,
     ));
-    lights.push(Sphere::new(Point3::new(1.0, 5.0, 4.0), 1.0, difflight));
 
     let lookfrom = Point3::new(26.0, 3.0, 6.0);
     let lookat = Point3::new(0.0, 2.0, 0.0);
@@ -414,10 +416,10 @@ pub fn simple_light(aspect_ratio: f32) -> (HittableList, Camera, Color, Hittable
         1.0,
     );
 
-    (world, cam, background, lights)
+    (vec![world], cam, background, vec![vec![]])
 }
 
-pub fn first_scene(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<HittableList>) {
+pub fn first_scene(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
     let mut world = HittableList::new();
     let background = Color::new(0.7, 0.8, 1.0);
 
@@ -467,7 +469,193 @@ pub fn first_scene(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<
         1.0,
     );
 
-    (vec![world], cam, background, vec![])
+    (vec![world], cam, background, vec![vec![]])
+}
+
+// the Cornell box from Ray Tracing: The Next Week, two white boxes and no meshes
+pub fn cornell_box_classic(
+    aspect_ratio: f32,
+) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
+    let background = Color::new(0.0, 0.0, 0.0);
+    let mut world = HittableList::new();
+
+    let red = Lambertian::new(SolidColorTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Lambertian::new(SolidColorTexture::new(Color::new(0.12, 0.45, 0.15)));
+    let light = DiffuseLight::new(SolidColorTexture::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.push(AARect::new(Plane::YZ, green, 0.0, 555.0, 0.0, 555.0, 555.0));
+    world.push(AARect::new(Plane::YZ, red, 0.0, 555.0, 0.0, 555.0, 0.0));
+    world.push(FlipFace::new(AARect::new(
+        Plane::XZ,
+        light,
+        213.0,
+        343.0,
+        227.0,
+        332.0,
+        554.0,
+    )));
+    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 0.0));
+    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
+    world.push(AARect::new(Plane::XY, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
+
+    let box1 = RectBox::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    let box1 = Rotate::new(box1, Axis::Y, 15.0);
+    world.push(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+
+    let box2 = RectBox::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 165.0, 165.0),
+        white,
+    );
+    let box2 = Rotate::new(box2, Axis::Y, -18.0);
+    world.push(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+
+    let cam = Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    (vec![world], cam, background, vec![vec![]])
+}
+
+pub fn two_checkered_spheres(
+    aspect_ratio: f32,
+) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
+    let mut world = HittableList::new();
+    let background = Color::new(0.7, 0.8, 1.0);
+
+    let checker = || {
+        Lambertian::new(CheckerTexture::new_color(
+            Color::new(0.2, 0.3, 0.1),
+            Color::new(0.9, 0.9, 0.9),
+        ))
+    };
+
+    world.push(Sphere::new(Point3::new(0.0, -10.0, 0.0), 10.0, checker()));
+    world.push(Sphere::new(Point3::new(0.0, 10.0, 0.0), 10.0, checker()));
+
+    let cam = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    (vec![world], cam, background, vec![vec![]])
+}
+
+pub fn two_perlin_spheres(
+    aspect_ratio: f32,
+) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
+    let mut world = HittableList::new();
+    let background = Color::new(0.7, 0.8, 1.0);
+
+    world.push(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(NoiseTexture::new(4.0)),
+    ));
+    world.push(Sphere::new(
+        Point3::new(0.0, 2.0, 0.0),
+        2.0,
+        Lambertian::new(NoiseTexture::new(4.0)),
+    ));
+
+    let cam = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    (vec![world], cam, background, vec![vec![]])
+}
+
+pub fn cornell_smoke(aspect_ratio: f32) -> (Vec<HittableList>, Camera, Color, Vec<LightList>) {
+    let mut world = HittableList::new();
+    let background = Color::new(0.0, 0.0, 0.0);
+
+    let red = Lambertian::new(SolidColorTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Lambertian::new(SolidColorTexture::new(Color::new(0.12, 0.45, 0.15)));
+    let light = DiffuseLight::new(SolidColorTexture::new(Color::new(7.0, 7.0, 7.0)));
+
+    world.push(AARect::new(Plane::YZ, green, 0.0, 555.0, 0.0, 555.0, 555.0));
+    world.push(AARect::new(Plane::YZ, red, 0.0, 555.0, 0.0, 555.0, 0.0));
+    world.push(FlipFace::new(AARect::new(
+        Plane::XZ,
+        light,
+        113.0,
+        443.0,
+        127.0,
+        432.0,
+        554.0,
+    )));
+    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 0.0));
+    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
+    world.push(AARect::new(Plane::XY, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
+
+    let box1 = RectBox::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    let box1 = Rotate::new(box1, Axis::Y, 15.0);
+    let box1 = Translate::new(box1, Vec3::new(265.0, 0.0, 295.0));
+    world.push(ConstantMedium::new(
+        box1,
+        0.01,
+        SolidColorTexture::new(Color::new(0.0, 0.0, 0.0)),
+    ));
+
+    let box2 = RectBox::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 165.0, 165.0),
+        white,
+    );
+    let box2 = Rotate::new(box2, Axis::Y, -18.0);
+    let box2 = Translate::new(box2, Vec3::new(130.0, 0.0, 65.0));
+    world.push(ConstantMedium::new(
+        box2,
+        0.01,
+        SolidColorTexture::new(Color::new(1.0, 1.0, 1.0)),
+    ));
+
+    let cam = Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    (vec![world], cam, background, vec![vec![]])
 }
 
 /*
@@ -568,39 +756,6 @@ fn random_scene_book() -> (HittableList, Camera, Color) {
     (world, cam, background)
 }
 
-fn two_checkered_spheres() -> (HittableList, Camera, Color) {
-    let mut world = HittableList::new();
-    let background = Color::new(0.7, 0.8, 1.0);
-
-    let checker = Lambertian::new_texture(Box::new(CheckerTexture::new_color(Color::new(0.1, 0.1, 0.1), Color::new(0.9, 0.9, 0.9))));
-
-    world.push(Box::new(Sphere::new(Point3::new(0.0, -10.0, 0.0), 10.0, checker)));
-
-    let checker = Lambertian::new_texture(Box::new(CheckerTexture::new_color(Color::new(0.1, 0.1, 0.1), Color::new(0.9, 0.9, 0.9))));
-    world.push(Box::new(Sphere::new(Point3::new(0.0, 10.0, 0.0), 10.0, checker)));
-
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let fov = 20.0;
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.0;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        fov,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0,
-    );
-
-    (world, cam, background)
-}
-
 fn polka_sphere() -> (HittableList, Camera, Color) {
     let mut world = HittableList::new();
     let background = Color::new(0.7, 0.8, 1.0);
@@ -632,38 +787,6 @@ fn polka_sphere() -> (HittableList, Camera, Color) {
     (world, cam, background)
 }
 
-fn two_perlin_spheres() -> (HittableList, Camera, Color) {
-    let mut world = HittableList::new();
-    let background = Color::new(0.7, 0.8, 1.0);
-
-    let pertext = NoiseTexture::new(4.0);
-    world.push(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new_texture(Box::new(pertext)))));
-
-    let pertext = NoiseTexture::new(4.0);
-    world.push(Box::new(Sphere::new(Point3::new(0.0, 2.0, 0.0), 2.0, Lambertian::new_texture(Box::new(pertext)))));
-
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let fov = 20.0;
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.0;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        fov,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0,
-    );
-
-    (world, cam, background)
-}
-
 fn image() -> (HittableList, Camera, Color) {
     let mut world = HittableList::new();
     let background = Color::new(0.7, 0.8, 1.0);
@@ -693,56 +816,20 @@ fn image() -> (HittableList, Camera, Color) {
     (world, cam, background)
 }
 
-fn cornell_smoke() -> (HittableList, Camera, Color) {
-    let mut world = HittableList::new();
-    let background = Color::new(0.0, 0.0, 0.0);
-
-    let red = Lambertian::new(SolidColorTexture::new(Color::new(0.65, 0.05, 0.05)));
-    let white = Lambertian::new(SolidColorTexture::new(Color::new(0.73, 0.73, 0.73)));
-    let green = Lambertian::new(SolidColorTexture::new(Color::new(0.12, 0.45, 0.15)));
-    let light = DiffuseLight::new(SolidColorTexture::new(Color::new(12.0, 6.807, 2.086)));
-
-    world.push(AARect::new(Plane::YZ, green, 0.0, 555.0, 0.0, 555.0, 555.0));
-    world.push(AARect::new(Plane::YZ, red, 0.0, 555.0, 0.0, 555.0, 0.0));
-    world.push(AARect::new(Plane::XZ, light, 177.0, 392.0, 163.0, 393.0, 554.0));
-    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 0.0));
-    world.push(AARect::new(Plane::XZ, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
-    world.push(AARect::new(Plane::XY, white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0));
-
-    let box1 = RectBox::new(Point3::new(130.0, 0.0, 65.0), Point3::new(295.0, 165.0, 230.0), white.clone());
-    let box1 = RotateY::new(box1, -18.0);
-    let box1 = Translate::new(box1, Vec3::new(0.0, 0.0, -30.0));
-    world.push(ConstantMedium::new(box1, 0.01, SolidColorTexture::new(Color::new(0.0, 0.0, 0.0))));
-
-    let box2 = RectBox::new(Point3::new(265.0, 0.0, 295.0), Point3::new(430.0, 330.0, 460.0), white);
-    let box2 = RotateY::new(box2, 15.0);
-    let box2 = Translate::new(box2, Vec3::new(-35.0, 0.0, 40.0));
-    world.push(ConstantMedium::new(box2, 0.01, SolidColorTexture::new(Color::new(1.0, 1.0, 1.0))));
-
-    let sphere_mat = NoiseTexture::new(5.0);
-    // let sphere = Sphere::new(Point3::new(170.5, 240.0, 117.5), 75.0, Lambertian::new(sphere_mat));
-    let sph = Sphere::new(Point3::new(277.0, 180.0, 350.0), 75.0, Lambertian::new(sphere_mat));
-    world.push(sph);
-
-    let lookfrom = Point3::new(278.0, 278.0, -800.0);
-    let lookat = Point3::new(278.0, 278.0, 0.0);
-    let fov = 40.0;
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.0;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        fov,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0,
-    );
+*/
 
-    (world, cam, background)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every frame needs its own list of lights, even an empty one
+    #[test]
+    fn scenes_have_lights_for_every_frame() {
+        for (name, scene) in SCENES {
+            // scenes whose assets aren't checked out can't be looked at
+            if let Ok((world, _, _, lights)) = scene(1.0) {
+                assert_eq!(lights.len(), world.len(), "{}", name);
+            }
+        }
+    }
 }
-*/
@@ -0,0 +1,557 @@
+use crate::sampler::Sampler;
+use crate::vec3::*;
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+// Wavelengths, in nanometers, that paths through dispersive materials pick from.
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 780.0;
+
+// CIE 1931 colour matching functions, from the multi-lobe fit of Wyman, Sloan and Shirley 2013,
+// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions".
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    let g = |mu: f32, below: f32, above: f32| {
+        let t = (lambda - mu) / if lambda < mu { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// linear sRGB, with the D65 white point, of CIE XYZ
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+// The share of each colour that light of one wavelength carries, through the CIE 1931 matching
+// functions, scaled so that wavelengths picked uniformly over the visible range average out to
+// white. Pure wavelengths lie outside the colours sRGB can show, so some of the channels are
+// negative; they cancel out over many samples.
+pub fn wavelength_weight(lambda: f32) -> Color {
+    static MEAN: OnceLock<Color> = OnceLock::new();
+    let mean = MEAN.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let sum = (0..steps).fold(Color::new_empty(), |sum, i| {
+            sum + xyz_to_rgb(cie_xyz(LAMBDA_MIN + i as f32 + 0.5))
+        });
+        sum / steps as f32
+    });
+    let c = xyz_to_rgb(cie_xyz(lambda));
+    Color::new(c.x / mean.x, c.y / mean.y, c.z / mean.z)
+}
+
+thread_local! {
+    static HERO_WAVELENGTH: Cell<Option<f32>> = const { Cell::new(None) };
+    static SPECTRAL: Cell<bool> = const { Cell::new(false) };
+}
+
+// Starts a camera path. Spectral paths follow a wavelength from the start, and see every colour
+// as the value of a spectrum at that wavelength; the others stay in RGB until they meet
+// dispersive glass.
+pub fn start_path(spectral: bool, sampler: &mut dyn Sampler) {
+    SPECTRAL.with(|s| s.set(spectral));
+    if spectral {
+        hero_wavelength(sampler);
+    }
+}
+
+// The wavelength the path being traced follows, picked with `sampler` the first time a
+// material asks for it. Paths that never ask stay in RGB.
+pub fn hero_wavelength(sampler: &mut dyn Sampler) -> f32 {
+    HERO_WAVELENGTH.with(|h| match h.get() {
+        Some(lambda) => lambda,
+        None => {
+            let lambda = LAMBDA_MIN + sampler.get_1d() * (LAMBDA_MAX - LAMBDA_MIN);
+            h.set(Some(lambda));
+            lambda
+        }
+    })
+}
+
+// The wavelength the last path picked, if it picked one, forgotten for the next path.
+pub fn take_hero_wavelength() -> Option<f32> {
+    HERO_WAVELENGTH.with(|h| h.take())
+}
+
+// the wavelength of the path being traced, if it is a spectral one
+pub fn spectral_wavelength() -> Option<f32> {
+    if SPECTRAL.with(|s| s.get()) {
+        HERO_WAVELENGTH.with(|h| h.get())
+    } else {
+        None
+    }
+}
+
+// An RGB colour as spectral paths see it: the value, at their wavelength, of a smooth spectrum
+// with that colour. Other paths get the colour back unchanged.
+pub fn uplift(rgb: Color) -> Color {
+    match spectral_wavelength() {
+        Some(lambda) => {
+            let v = SigmoidSpectrum::new(rgb).value(lambda);
+            Color::new(v, v, v)
+        }
+        None => rgb,
+    }
+}
+
+// brightness below which colours aren't looked up as they are
+const DARKEST_FIT: f32 = 0.2;
+
+// A smooth spectrum of a given colour, after Jakob and Hanika 2019, "A Low-Dimensional Function
+// Space for Efficient Spectral Upsampling": a quadratic in the wavelength squeezed into 0..1 by
+// a sigmoid, times a scale for colours brighter than white.
+struct SigmoidSpectrum {
+    c: [f32; 3],
+    scale: f32,
+}
+
+impl SigmoidSpectrum {
+    fn new(rgb: Color) -> Self {
+        let rgb = Color::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0));
+        let m = rgb.x.max(rgb.y).max(rgb.z);
+        if rgb.x == rgb.y && rgb.y == rgb.z {
+            // flat, and exactly the grey it stands for
+            return Self {
+                c: [0.0; 3],
+                scale: 2.0 * m,
+            };
+        }
+        if m < DARKEST_FIT {
+            // the fits are far apart this close to black, so a dark colour is a dimmer copy of
+            // the spectrum of a brighter one of the same hue
+            let scale = m / DARKEST_FIT;
+            Self {
+                c: sigmoid_table().lookup(rgb / scale),
+                scale,
+            }
+        } else if m <= 1.0 {
+            Self {
+                c: sigmoid_table().lookup(rgb),
+                scale: 1.0,
+            }
+        } else {
+            // a reflectance of half the colour, kept away from the sigmoid's asymptotes
+            let scale = 2.0 * m;
+            Self {
+                c: sigmoid_table().lookup(rgb / scale),
+                scale,
+            }
+        }
+    }
+
+    fn value(&self, lambda: f32) -> f32 {
+        let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+        self.scale * sigmoid((self.c[0] * t + self.c[1]) * t + self.c[2])
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+// Coefficients of the sigmoid spectra of colours up to white. Colours are indexed by their
+// largest channel, its value and the other two as fractions of it. A column of the table, every
+// value of one hue, is fit the first time a colour needs it, so a render only pays for the hues
+// its scene has.
+const TABLE_RES: usize = 24;
+// wavelengths the fits compare colours at
+const FIT_SAMPLES: usize = 80;
+const FIT_ITERATIONS: usize = 50;
+
+struct SigmoidTable {
+    values: [f32; TABLE_RES],
+    // film weights at the fit's wavelengths
+    weights: Vec<[f64; 3]>,
+    // [largest channel][second fraction][first fraction], each column by value
+    columns: Vec<OnceLock<Vec<[f32; 3]>>>,
+}
+
+fn sigmoid_table() -> &'static SigmoidTable {
+    static TABLE: OnceLock<SigmoidTable> = OnceLock::new();
+    TABLE.get_or_init(SigmoidTable::new)
+}
+
+fn smoothstep(x: f64) -> f64 {
+    x * x * (3.0 - 2.0 * x)
+}
+
+impl SigmoidTable {
+    fn new() -> Self {
+        let n = TABLE_RES;
+        let mut values = [0.0; TABLE_RES];
+        for (k, v) in values.iter_mut().enumerate() {
+            *v = smoothstep(smoothstep(k as f64 / (n - 1) as f64)) as f32;
+        }
+
+        let weights = (0..FIT_SAMPLES)
+            .map(|i| {
+                let step = (LAMBDA_MAX - LAMBDA_MIN) / FIT_SAMPLES as f32;
+                let w = wavelength_weight(LAMBDA_MIN + (i as f32 + 0.5) * step);
+                [w.x as f64, w.y as f64, w.z as f64]
+            })
+            .collect();
+
+        Self {
+            values,
+            weights,
+            columns: (0..3 * n * n).map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    fn column(&self, l: usize, j: usize, i: usize) -> &[[f32; 3]] {
+        let n = TABLE_RES;
+        self.columns[(l * n + j) * n + i].get_or_init(|| self.fit_column(l, j, i))
+    }
+
+    // Each fit starts from the solution of its neighbour, going up and down from a middling
+    // brightness, where the sigmoid is far from its asymptotes and the fit is easy.
+    fn fit_column(&self, l: usize, j: usize, i: usize) -> Vec<[f32; 3]> {
+        let n = TABLE_RES;
+        let (x, y) = (i as f64 / (n - 1) as f64, j as f64 / (n - 1) as f64);
+        let mut out = vec![[0.0; 3]; n];
+        let mut fit_at = |k: usize, start: [f64; 3]| {
+            let z = self.values[k] as f64;
+            let mut rgb = [0.0; 3];
+            rgb[l] = z;
+            rgb[(l + 1) % 3] = x * z;
+            rgb[(l + 2) % 3] = y * z;
+            let c = fit_sigmoid(rgb, start, &self.weights);
+            out[k] = [c[0] as f32, c[1] as f32, c[2] as f32];
+            c
+        };
+
+        let middle = n / 5;
+        let first = fit_at(middle, [0.0; 3]);
+        let mut c = first;
+        for k in middle + 1..n {
+            c = fit_at(k, c);
+        }
+        let mut c = first;
+        for k in (0..middle).rev() {
+            c = fit_at(k, c);
+        }
+        out
+    }
+
+    // sigmoid coefficients of a colour no brighter than white, interpolated between the fits
+    fn lookup(&self, rgb: Color) -> [f32; 3] {
+        let rgb = [rgb.x, rgb.y, rgb.z];
+        let l = if rgb[0] >= rgb[1] && rgb[0] >= rgb[2] {
+            0
+        } else if rgb[1] >= rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z = rgb[l];
+        if z <= 0.0 {
+            return [0.0, 0.0, f32::NEG_INFINITY];
+        }
+
+        let n = TABLE_RES;
+        let cell = |v: f32| {
+            let v = v.clamp(0.0, 1.0) * (n - 1) as f32;
+            let i = (v as usize).min(n - 2);
+            (i, v - i as f32)
+        };
+        let (xi, fx) = cell(rgb[(l + 1) % 3] / z);
+        let (yi, fy) = cell(rgb[(l + 2) % 3] / z);
+        let zi = (self.values.partition_point(|&v| v <= z).max(1) - 1).min(n - 2);
+        let fz = ((z - self.values[zi]) / (self.values[zi + 1] - self.values[zi])).clamp(0.0, 1.0);
+
+        let mut c = [0.0; 3];
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let column = self.column(l, yi + dy, xi + dx);
+                for (dz, wz) in [(0, 1.0 - fz), (1, fz)] {
+                    let w = wx * wy * wz;
+                    for (c, e) in c.iter_mut().zip(&column[zi + dz]) {
+                        *c += w * e;
+                    }
+                }
+            }
+        }
+        c
+    }
+}
+
+// colour of the sigmoid spectrum with coefficients `c`, as the film sees it
+fn sigmoid_rgb(c: [f64; 3], weights: &[[f64; 3]]) -> [f64; 3] {
+    let mut rgb = [0.0; 3];
+    for (i, w) in weights.iter().enumerate() {
+        let t = (i as f64 + 0.5) / weights.len() as f64;
+        let x = (c[0] * t + c[1]) * t + c[2];
+        let s = 0.5 + x / (2.0 * (1.0 + x * x).sqrt());
+        for (rgb, w) in rgb.iter_mut().zip(w) {
+            *rgb += s * w;
+        }
+    }
+    rgb.map(|v| v / weights.len() as f64)
+}
+
+// Levenberg-Marquardt from `start` towards the coefficients whose spectrum has colour `rgb`:
+// Gauss-Newton steps, shortened towards plain gradient descent while they overshoot, which
+// saturated colours with their steep sigmoids otherwise do.
+fn fit_sigmoid(rgb: [f64; 3], start: [f64; 3], weights: &[[f64; 3]]) -> [f64; 3] {
+    const EPS: f64 = 1e-4;
+    let residual = |c: [f64; 3]| {
+        let current = sigmoid_rgb(c, weights);
+        [
+            rgb[0] - current[0],
+            rgb[1] - current[1],
+            rgb[2] - current[2],
+        ]
+    };
+    let error = |r: [f64; 3]| r.iter().map(|r| r * r).sum::<f64>();
+
+    let mut c = start;
+    let mut r = residual(c);
+    let mut damping = 1e-3;
+    for _ in 0..FIT_ITERATIONS {
+        if error(r) < 1e-12 {
+            break;
+        }
+
+        let mut jacobian = [[0.0; 3]; 3];
+        for k in 0..3 {
+            let (mut above, mut below) = (c, c);
+            above[k] += EPS;
+            below[k] -= EPS;
+            let (a, b) = (sigmoid_rgb(above, weights), sigmoid_rgb(below, weights));
+            for (row, (a, b)) in jacobian.iter_mut().zip(a.iter().zip(&b)) {
+                row[k] = (a - b) / (2.0 * EPS);
+            }
+        }
+        // the normal equations, J^T J + damping diag(J^T J), and J^T r
+        let mut jtj = [[0.0; 3]; 3];
+        let mut jtr = [0.0; 3];
+        for a in 0..3 {
+            for b in 0..3 {
+                jtj[a][b] = (0..3).map(|i| jacobian[i][a] * jacobian[i][b]).sum();
+            }
+            jtr[a] = (0..3).map(|i| jacobian[i][a] * r[i]).sum();
+        }
+
+        let mut improved = false;
+        while damping < 1e10 {
+            let mut damped = jtj;
+            for (a, row) in damped.iter_mut().enumerate() {
+                row[a] += damping * jtj[a][a].max(1e-12);
+            }
+            if let Some(step) = solve3(damped, jtr) {
+                let mut next = [c[0] + step[0], c[1] + step[1], c[2] + step[2]];
+                // past this the sigmoid is a step anyway, and the fit only loses precision
+                let largest = next.iter().fold(0.0f64, |m, c| m.max(c.abs()));
+                if largest > 200.0 {
+                    next = next.map(|c| c * 200.0 / largest);
+                }
+                let next_r = residual(next);
+                if error(next_r) < error(r) {
+                    c = next;
+                    r = next_r;
+                    damping = (damping / 3.0).max(1e-9);
+                    improved = true;
+                    break;
+                }
+            }
+            damping *= 4.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+    c
+}
+
+// `x` with `a x = b`, by Cramer's rule. None if `a` is singular.
+fn solve3(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(a);
+    if d == 0.0 || !d.is_finite() {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (k, x) in x.iter_mut().enumerate() {
+        let mut m = a;
+        for (row, b) in m.iter_mut().zip(&b) {
+            row[k] = *b;
+        }
+        *x = det(m) / d;
+    }
+    Some(x)
+}
+
+// A spectrum given by the scene: of an emitter, like a blackbody or a standard illuminant, or a
+// measured reflectance.
+#[derive(Debug, Clone)]
+pub enum Spectrum {
+    // Planck's law at a temperature in kelvin, in arbitrary units
+    Blackbody(f32),
+    // linear between samples at increasing wavelengths, in nanometers, and constant past the
+    // first and the last
+    Sampled {
+        wavelengths: Vec<f32>,
+        values: Vec<f32>,
+    },
+}
+
+// CIE standard illuminant D65, average daylight, every 10 nm from 380 to 780
+const D65: [f32; 41] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86, 115.92, 108.81,
+    109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33, 95.79, 88.69, 90.01, 89.60,
+    87.70, 83.29, 83.70, 80.03, 80.21, 82.28, 78.28, 69.72, 71.61, 74.35, 61.60, 69.89, 75.09,
+    63.59, 46.42, 66.81, 63.38,
+];
+
+impl Spectrum {
+    pub const ILLUMINANT_NAMES: &'static str = "a, d65, e";
+
+    // CIE standard illuminants: A, incandescent light, D65, daylight, and E, equal energy
+    pub fn illuminant(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "a" => Some(Spectrum::Blackbody(2856.0)),
+            "d65" => Some(Spectrum::Sampled {
+                wavelengths: (0..D65.len())
+                    .map(|i| LAMBDA_MIN + 10.0 * i as f32)
+                    .collect(),
+                values: D65.to_vec(),
+            }),
+            "e" => Some(Spectrum::Sampled {
+                wavelengths: vec![LAMBDA_MIN],
+                values: vec![1.0],
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self, lambda: f32) -> f32 {
+        match self {
+            Spectrum::Blackbody(temperature) => {
+                const H: f64 = 6.626_070_15e-34;
+                const C: f64 = 2.997_924_58e8;
+                const K: f64 = 1.380_649e-23;
+                let l = lambda as f64 * 1e-9;
+                let t = *temperature as f64;
+                // scaled from W / (sr m^3) to keep f32 happy
+                (2.0 * H * C * C / l.powi(5) / ((H * C / (l * K * t)).exp() - 1.0) * 1e-12) as f32
+            }
+            Spectrum::Sampled {
+                wavelengths,
+                values,
+            } => {
+                let i = wavelengths.partition_point(|&w| w <= lambda);
+                if i == 0 {
+                    values[0]
+                } else if i == wavelengths.len() {
+                    values[i - 1]
+                } else {
+                    let t = (lambda - wavelengths[i - 1]) / (wavelengths[i] - wavelengths[i - 1]);
+                    values[i - 1] + t * (values[i] - values[i - 1])
+                }
+            }
+        }
+    }
+
+    // the colour RGB paths use for it, the one spectral paths average out to
+    pub fn to_rgb(&self) -> Color {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let sum = (0..steps).fold(Color::new_empty(), |sum, i| {
+            let lambda = LAMBDA_MIN + i as f32 + 0.5;
+            sum + self.value(lambda) * wavelength_weight(lambda)
+        });
+        sum / steps as f32
+    }
+}
+
+// How glass bends light of each wavelength, in nanometers.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / λ², λ in micrometers
+    Cauchy { a: f32, b: f32 },
+    // n² = 1 + Σ b λ² / (λ² - c), λ in micrometers
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+// the Fraunhofer lines glass is usually described at
+const LAMBDA_D: f32 = 587.6;
+const LAMBDA_F: f32 = 486.1;
+const LAMBDA_C: f32 = 656.3;
+
+impl Dispersion {
+    // Cauchy's equation fit to an index of refraction `ir` at the d line and an Abbe number,
+    // (n_d - 1) / (n_F - n_C): around 60 for crown glass, 30 for flint glass, 55 for diamond.
+    // The lower, the wider the rainbow.
+    pub fn from_abbe(ir: f32, abbe: f32) -> Self {
+        let inv_sq = |lambda: f32| 1.0 / (lambda * 1e-3).powi(2);
+        let b = (ir - 1.0) / (abbe * (inv_sq(LAMBDA_F) - inv_sq(LAMBDA_C)));
+        Dispersion::Cauchy {
+            a: ir - b * inv_sq(LAMBDA_D),
+            b,
+        }
+    }
+
+    pub fn ior(&self, lambda: f32) -> f32 {
+        let um2 = (lambda * 1e-3).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / um2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = b.iter().zip(&c).map(|(b, c)| b * um2 / (um2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    // index of refraction at the d line, for when there is no wavelength to go by
+    pub fn ior_d(&self) -> f32 {
+        self.ior(LAMBDA_D)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the colour a spectrum averages out to over wavelengths picked uniformly
+    fn to_rgb(spectrum: &SigmoidSpectrum) -> Color {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let sum = (0..steps).fold(Color::new_empty(), |sum, i| {
+            let lambda = LAMBDA_MIN + i as f32 + 0.5;
+            sum + spectrum.value(lambda) * wavelength_weight(lambda)
+        });
+        sum / steps as f32
+    }
+
+    #[test]
+    fn uplifted_colours_round_trip() {
+        let colours = [
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.65, 0.05, 0.05),
+            Color::new(0.12, 0.45, 0.15),
+            Color::new(0.1, 0.2, 0.7),
+            Color::new(0.8, 0.85, 0.88),
+            Color::new(0.9, 0.6, 0.1),
+            Color::new(0.02, 0.01, 0.03),
+            Color::new(12.0, 6.807, 2.086),
+        ];
+        for &rgb in &colours {
+            let back = to_rgb(&SigmoidSpectrum::new(rgb));
+            let error = (back - rgb).length() / rgb.length();
+            assert!(error < 0.02, "{:?} came back as {:?}", rgb, back);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::material::Material;
 use crate::onb::ONB;
 use crate::pdf;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::*;
 
 use std::f32::consts::{FRAC_PI_2, PI};
@@ -32,25 +33,7 @@ impl<M: Material> Sphere<M> {
 
 impl<M: Sync + Send + Material> Hittable for Sphere<M> {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.orig - self.center;
-        let a = r.dir.length_squared();
-        let half_b = oc.dot(r.dir);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-        let sqrtd = discriminant.sqrt();
-
-        // Find the nearest root that lies in the acceptable range.
-        let root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            let root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
+        let root = hit_distance(r, self.center, self.radius, t_min, t_max)?;
 
         let mut hr = HitRecord {
             normal: Vec3::new_empty(),
@@ -80,9 +63,8 @@ impl<M: Sync + Send + Material> Hittable for Sphere<M> {
 
     fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
         if let Some(_) = self.hit(&Ray::new(orig, v, 0.0), 0.001, std::f32::INFINITY) {
-            let cos_theta_max =
-                (1.0 - self.radius.powi(2) / (self.center - orig).length_squared()).sqrt();
-            let solid_angle = 2.0 * std::f32::consts::PI * (1.0 - cos_theta_max);
+            let distance_squared = (self.center - orig).length_squared();
+            let solid_angle = pdf::sphere_solid_angle(self.radius, distance_squared);
 
             1.0 / solid_angle
         } else {
@@ -90,11 +72,23 @@ impl<M: Sync + Send + Material> Hittable for Sphere<M> {
         }
     }
 
-    fn random(&self, orig: Vec3) -> Vec3 {
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
         let dir = self.center - orig;
         let distance_squared = dir.length_squared();
         let onb = ONB::build_from_w(dir);
-        onb.local_vec3(pdf::random_to_sphere(self.radius, distance_squared))
+        onb.local_vec3(pdf::random_to_sphere(
+            self.radius,
+            distance_squared,
+            sampler.get_2d(),
+        ))
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.material.is_emitter()
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * std::f32::consts::PI * self.radius * self.radius
     }
 }
 
@@ -141,25 +135,7 @@ impl<M: Material> MovingSphere<M> {
 
 impl<M: Sync + Send + Material> Hittable for MovingSphere<M> {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.orig - self.calc_time(r.time);
-        let a = r.dir.length_squared();
-        let half_b = oc.dot(r.dir);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-        let sqrtd = discriminant.sqrt();
-
-        // Find the nearest root that lies in the acceptable range.
-        let root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            let root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
+        let root = hit_distance(r, self.calc_time(r.time), self.radius, t_min, t_max)?;
 
         let mut hr = HitRecord {
             normal: Vec3::new_empty(),
@@ -196,9 +172,8 @@ impl<M: Sync + Send + Material> Hittable for MovingSphere<M> {
 
     fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
         if let Some(_) = self.hit(&Ray::new(orig, v, 0.0), 0.001, std::f32::INFINITY) {
-            let cos_theta_max =
-                (1.0 - self.radius.powi(2) / (self.center0 - orig).length_squared()).sqrt();
-            let solid_angle = 2.0 * std::f32::consts::PI * (1.0 - cos_theta_max);
+            let distance_squared = (self.center0 - orig).length_squared();
+            let solid_angle = pdf::sphere_solid_angle(self.radius, distance_squared);
 
             1.0 / solid_angle
         } else {
@@ -206,12 +181,59 @@ impl<M: Sync + Send + Material> Hittable for MovingSphere<M> {
         }
     }
 
-    fn random(&self, orig: Vec3) -> Vec3 {
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
         let dir = self.center0 - orig;
         let distance_squared = dir.length_squared();
         let onb = ONB::build_from_w(dir);
-        onb.local_vec3(pdf::random_to_sphere(self.radius, distance_squared))
+        onb.local_vec3(pdf::random_to_sphere(
+            self.radius,
+            distance_squared,
+            sampler.get_2d(),
+        ))
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.material.is_emitter()
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * std::f32::consts::PI * self.radius * self.radius
+    }
+}
+
+// how far from a sphere's surface, relative to the size of its coordinates, a ray origin still
+// counts as on it
+const SURFACE_EPSILON: f32 = 1e-5;
+
+// Distance along `r` to the nearest point of the sphere in the acceptable range. Hit points are
+// only accurate to a few ulps of their coordinates, so a ray spawned on a big sphere may start
+// just inside it; one heading outward from there has left the sphere and must not hit it again
+// from inside.
+fn hit_distance(r: &Ray, center: Point3, radius: f32, t_min: f32, t_max: f32) -> Option<f32> {
+    let oc = r.orig - center;
+    let a = r.dir.length_squared();
+    let half_b = oc.dot(r.dir);
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+
+    // Find the nearest root that lies in the acceptable range.
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        let tolerance = SURFACE_EPSILON * (center.length() + radius);
+        if half_b > 0.0 && (oc.length() - radius).abs() <= tolerance {
+            return None;
+        }
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return None;
+        }
     }
+    Some(root)
 }
 
 fn get_sphere_uv(p: Point3) -> (f32, f32) {
@@ -0,0 +1,206 @@
+use crate::cli::{Options, SceneSource};
+use crate::film::{Film, Pixel};
+use crate::vec3::*;
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"REIS";
+const VERSION: u32 = 2;
+
+// The state of an interrupted render is the accumulated film, whose sample counts say how far
+// along the sample sequence each pixel got, and a hash of whatever determines the image, so a
+// state is never resumed against a different scene or settings.
+//
+// Layout, all little endian: magic, version, hash, width, height, then for every pixel the
+// radiance sum (3 x f32), the weight (f32), the sample count (u32) and the luminance mean and
+// squared deviations (2 x f32).
+pub fn save(path: &str, hash: u64, film: &Film) -> io::Result<()> {
+    // written next to the target and renamed over it, so a render killed while saving
+    // still leaves the previous state intact
+    let tmp = format!("{}.partial", path);
+    {
+        let mut f = BufWriter::new(File::create(&tmp)?);
+        f.write_all(MAGIC)?;
+        f.write_all(&VERSION.to_le_bytes())?;
+        f.write_all(&hash.to_le_bytes())?;
+        f.write_all(&(film.width as u32).to_le_bytes())?;
+        f.write_all(&(film.height as u32).to_le_bytes())?;
+
+        for p in film.pixels() {
+            for v in &[p.sum.x, p.sum.y, p.sum.z, p.weight] {
+                f.write_all(&v.to_le_bytes())?;
+            }
+            f.write_all(&p.samples.to_le_bytes())?;
+            f.write_all(&p.lum_mean.to_le_bytes())?;
+            f.write_all(&p.lum_m2.to_le_bytes())?;
+        }
+        f.flush()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+// The header is checked against the render being resumed before anything is allocated, so a
+// stale or corrupt file can't ask for an arbitrarily large film.
+pub fn load(path: &str, hash: u64, width: usize, height: usize) -> io::Result<Film> {
+    let mut f = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; 4];
+    f.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a render state file"));
+    }
+    let version = read_u32(&mut f)?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported state version {}", version)));
+    }
+
+    if read_u64(&mut f)? != hash {
+        return Err(invalid(
+            "saved for a different scene or settings, refusing to resume",
+        ));
+    }
+    let (w, h) = (read_u32(&mut f)? as usize, read_u32(&mut f)? as usize);
+    if (w, h) != (width, height) {
+        return Err(invalid(&format!(
+            "saved at {}x{}, not {}x{}",
+            w, h, width, height
+        )));
+    }
+
+    let mut film = Film::new(width, height);
+    for p in film.pixels_mut() {
+        let sum = Color::new(read_f32(&mut f)?, read_f32(&mut f)?, read_f32(&mut f)?);
+        *p = Pixel {
+            sum,
+            weight: read_f32(&mut f)?,
+            samples: read_u32(&mut f)?,
+            lum_mean: read_f32(&mut f)?,
+            lum_m2: read_f32(&mut f)?,
+        };
+    }
+
+    Ok(film)
+}
+
+// Hash of the settings that change what a sample contributes: the scene, the sampler, integrator,
+// light sampler, spectral mode and seed, the frame, the resolution and the depth limits. Samples
+// per pixel, tiling and output options are left out so a resumed render can ask for more samples
+// or a different format.
+pub fn settings_hash(opts: &Options, frame: usize) -> io::Result<u64> {
+    let mut h = Fnv1a::new();
+
+    match &opts.scene {
+        SceneSource::Builtin(name) => {
+            h.write(b"builtin");
+            h.write(name.as_bytes());
+        }
+        SceneSource::File(path) => {
+            h.write(b"file");
+            h.write(&fs::read(Path::new(path))?);
+        }
+    }
+
+    h.write(opts.sampler.name().as_bytes());
+    h.write(opts.integrator.name().as_bytes());
+    h.write(opts.light_sampler.name().as_bytes());
+    if opts.spectral {
+        h.write(b"spectral");
+    }
+    for v in &[
+        opts.seed as u64,
+        frame as u64,
+        opts.width as u64,
+        opts.height as u64,
+        opts.max_depth as u64,
+        opts.max_diffuse as u64,
+        opts.max_specular as u64,
+        opts.max_volume as u64,
+        opts.rr_depth as u64,
+    ] {
+        h.write(&v.to_le_bytes());
+    }
+
+    Ok(h.finish())
+}
+
+// 64 bit FNV-1a. std's hasher is not guaranteed to give the same result across releases, and
+// state files should outlive the binary that wrote them.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(f: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    f.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(f: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    f.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f32(f: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    f.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{self, Command};
+
+    fn options(args: &[&str]) -> Options {
+        match cli::parse_args(args.iter().map(|a| a.to_string())) {
+            Ok(Command::Render(opts)) => *opts,
+            _ => panic!("bad test arguments {:?}", args),
+        }
+    }
+
+    #[test]
+    fn refuses_state_from_other_settings() {
+        let path = std::env::temp_dir().join(format!("rei-state-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let saved = options(&["-w", "8", "--height", "4", "--seed", "1"]);
+        let mut film = Film::new(8, 4);
+        film.pixels_mut()[5].add_sample(Color::new(1.0, 2.0, 3.0), 1.0);
+        save(path, settings_hash(&saved, 0).unwrap(), &film).unwrap();
+
+        let resumed = load(path, settings_hash(&saved, 0).unwrap(), 8, 4).unwrap();
+        assert_eq!(resumed.pixels()[5].samples, 1);
+
+        let reseeded = options(&["-w", "8", "--height", "4", "--seed", "2"]);
+        assert!(load(path, settings_hash(&reseeded, 0).unwrap(), 8, 4).is_err());
+        assert!(load(path, settings_hash(&saved, 1).unwrap(), 8, 4).is_err());
+
+        // a matching hash with other dimensions is refused before the film is allocated
+        let resized = load(path, settings_hash(&saved, 0).unwrap(), 1 << 20, 1 << 20);
+        assert!(resized.is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
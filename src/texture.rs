@@ -1,10 +1,19 @@
 use crate::perlin::Perlin;
+use crate::spectrum::{self, Spectrum};
 use crate::vec3::{Color, Point3, Vec3};
 
+use std::sync::Arc;
+
 pub trait Texture: Sync + Send {
     fn value(&self, u: f32, v: f32, p: Point3) -> Color;
 }
 
+impl Texture for Arc<dyn Texture> {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        (**self).value(u, v, p)
+    }
+}
+
 #[derive(Clone)]
 pub struct SolidColorTexture {
     color_value: Color,
@@ -24,7 +33,7 @@ impl SolidColorTexture {
 
 impl Texture for SolidColorTexture {
     fn value(&self, _u: f32, _v: f32, _p: Point3) -> Color {
-        self.color_value
+        spectrum::uplift(self.color_value)
     }
 }
 
@@ -153,20 +162,15 @@ pub struct ImageTexture {
 
 impl ImageTexture {
     pub fn new(path: &str) -> Self {
-        let image = image::open(path).expect("image not found").to_rgb();
+        Self::open(path).expect("image not found")
+    }
+
+    pub fn open(path: &str) -> image::ImageResult<Self> {
+        let image = image::open(path)?.to_rgb();
         let (nx, ny) = image.dimensions();
         let data = image.into_raw();
 
-        /*
-        for y in 0..ny {
-            for x in 0..nx {
-                let idx = (3 * x + 3 * nx * y) as usize;
-                println!("{} {} {}", data[idx], data[idx + 1], data[idx + 2]);
-            }
-        }
-        */
-
-        ImageTexture { data, nx, ny }
+        Ok(ImageTexture { data, nx, ny })
     }
 }
 
@@ -196,6 +200,43 @@ impl Texture for ImageTexture {
         let g = self.data[idx + 1] as f32 / 255.0;
         let b = self.data[idx + 2] as f32 / 255.0;
 
-        Color::new(r, g, b)
+        spectrum::uplift(Color::new(r, g, b))
+    }
+}
+
+// A colour given as a spectrum rather than in RGB: spectral paths see the spectrum itself, the
+// others the colour it averages out to.
+pub struct SpectrumTexture {
+    spectrum: Spectrum,
+    scale: f32,
+    rgb: Color,
+}
+
+impl SpectrumTexture {
+    pub fn new(spectrum: Spectrum, scale: f32) -> Self {
+        let rgb = scale * spectrum.to_rgb();
+        Self {
+            spectrum,
+            scale,
+            rgb,
+        }
+    }
+
+    // light with the colour of `spectrum`, as bright as a white of `intensity`
+    pub fn illuminant(spectrum: Spectrum, intensity: f32) -> Self {
+        let luminance = spectrum.to_rgb().luminance();
+        Self::new(spectrum, intensity / luminance)
+    }
+}
+
+impl Texture for SpectrumTexture {
+    fn value(&self, _u: f32, _v: f32, _p: Point3) -> Color {
+        match spectrum::spectral_wavelength() {
+            Some(lambda) => {
+                let v = self.scale * self.spectrum.value(lambda);
+                Color::new(v, v, v)
+            }
+            None => self.rgb,
+        }
     }
 }
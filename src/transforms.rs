@@ -2,10 +2,13 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::matrix4::Matrix4;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::*;
 
 use std::f32;
+use std::sync::Arc;
 
+#[derive(Clone, Copy)]
 pub enum Axis {
     X,
     Y,
@@ -36,8 +39,10 @@ impl Hittable for Transform {
             r.time,
         );
         if let Some(mut hit) = self.hit.hit(&transformed_ray, t_min, t_max) {
-            hit.p = self.inv_transform * r.orig;
-            hit.normal = self.inv_transform.mul_as_33(hit.normal);
+            // transform_mat goes from world to object space, so normals come back through its
+            // transpose
+            hit.p = self.inv_transform * hit.p;
+            hit.normal = self.transform_mat.transpose().mul_as_33(hit.normal).unit_vector();
             return Some(hit);
         }
         None
@@ -75,6 +80,55 @@ impl Hittable for Transform {
 
         None
     }
+
+    // Directions bunch up where the transform squeezes space and spread out where it stretches
+    // it, so the child's density changes by the cube of how far a unit direction gets stretched,
+    // over the change in volume.
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        let local = self.transform_mat.mul_as_33(v);
+        let pdf = self.hit.pdf_value(self.transform_mat * orig, local);
+        let stretch = self.inv_transform.mul_as_33(local.unit_vector()).length();
+        pdf * stretch.powi(3) / self.inv_transform.determinant_33().abs()
+    }
+
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.inv_transform
+            .mul_as_33(self.hit.random(self.transform_mat * orig, sampler))
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.hit.is_area_light()
+    }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        let mut inner = vec![];
+        self.hit.area_lights(&mut inner);
+        for light in inner {
+            lights.push(Arc::new(Self {
+                hit: Box::new(light),
+                transform_mat: self.transform_mat,
+                inv_transform: self.inv_transform,
+            }));
+        }
+    }
+
+    // A surface's area changes by the change in volume times the length of its normal through
+    // the inverse transpose. Shapes without a single normal are taken to stretch evenly.
+    fn area(&self) -> f32 {
+        let det = self.inv_transform.determinant_33().abs();
+        match self.hit.facing() {
+            Some(normal) => {
+                self.hit.area() * det * self.transform_mat.transpose().mul_as_33(normal).length()
+            }
+            None => self.hit.area() * det.powf(2.0 / 3.0),
+        }
+    }
+
+    fn facing(&self) -> Option<Vec3> {
+        self.hit
+            .facing()
+            .map(|n| self.transform_mat.transpose().mul_as_33(n).unit_vector())
+    }
 }
 
 pub struct Translate {
@@ -113,10 +167,39 @@ impl Hittable for Translate {
             None
         }
     }
+
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        self.hit.pdf_value(orig - self.offset, v)
+    }
+
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.hit.random(orig - self.offset, sampler)
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.hit.is_area_light()
+    }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        let mut inner = vec![];
+        self.hit.area_lights(&mut inner);
+        for light in inner {
+            lights.push(Arc::new(Translate::new(light, self.offset)));
+        }
+    }
+
+    fn area(&self) -> f32 {
+        self.hit.area()
+    }
+
+    fn facing(&self) -> Option<Vec3> {
+        self.hit.facing()
+    }
 }
 
 pub struct Rotate {
     axis: Axis,
+    angle: f32,
     hit: Box<dyn Hittable>,
     sin_theta: f32,
     cos_theta: f32,
@@ -178,41 +261,44 @@ impl Rotate {
             cos_theta,
             bbox,
             axis,
+            angle,
         }
     }
-}
 
-impl Hittable for Rotate {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let (a, b) = match self.axis {
+    fn axes(&self) -> (usize, usize) {
+        match self.axis {
             Axis::X => (1, 2),
             Axis::Y => (0, 2),
             Axis::Z => (0, 1),
-        };
-
-        let mut origin = r.orig;
-        let mut dir = r.dir;
+        }
+    }
 
-        origin[a] = self.cos_theta * r.orig[a] - self.sin_theta * r.orig[b];
-        origin[b] = self.sin_theta * r.orig[a] + self.cos_theta * r.orig[b];
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        let (a, b) = self.axes();
+        let mut ret = v;
+        ret[a] = self.cos_theta * v[a] - self.sin_theta * v[b];
+        ret[b] = self.sin_theta * v[a] + self.cos_theta * v[b];
+        ret
+    }
 
-        dir[a] = self.cos_theta * r.dir[a] - self.sin_theta * r.dir[b];
-        dir[b] = self.sin_theta * r.dir[a] + self.cos_theta * r.dir[b];
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        let (a, b) = self.axes();
+        let mut ret = v;
+        ret[a] = self.cos_theta * v[a] + self.sin_theta * v[b];
+        ret[b] = -self.sin_theta * v[a] + self.cos_theta * v[b];
+        ret
+    }
+}
 
-        let rotated_r = Ray::new(origin, dir, r.time);
+impl Hittable for Rotate {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let rotated_r = Ray::new(self.to_object(r.orig), self.to_object(r.dir), r.time);
 
         if let Some(rec) = self.hit.hit(&rotated_r, t_min, t_max) {
-            let mut p = rec.p;
-            let mut normal = rec.normal;
-
-            p[a] = self.cos_theta * rec.p[a] + self.sin_theta * rec.p[b];
-            p[b] = -self.sin_theta * rec.p[a] + self.cos_theta * rec.p[b];
-
-            normal[a] = self.cos_theta * rec.normal[a] + self.sin_theta * rec.normal[b];
-            normal[b] = -self.sin_theta * rec.normal[a] + self.cos_theta * rec.normal[b];
+            let normal = self.to_world(rec.normal);
 
             let mut ret = HitRecord {
-                p,
+                p: self.to_world(rec.p),
                 normal,
                 t: rec.t,
                 u: rec.u,
@@ -232,24 +318,74 @@ impl Hittable for Rotate {
     fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
         Some(self.bbox.clone())
     }
+
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        self.hit.pdf_value(self.to_object(orig), self.to_object(v))
+    }
+
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.to_world(self.hit.random(self.to_object(orig), sampler))
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.hit.is_area_light()
+    }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        let mut inner = vec![];
+        self.hit.area_lights(&mut inner);
+        for light in inner {
+            lights.push(Arc::new(Rotate::new(light, self.axis, self.angle)));
+        }
+    }
+
+    fn area(&self) -> f32 {
+        self.hit.area()
+    }
+
+    fn facing(&self) -> Option<Vec3> {
+        self.hit.facing().map(|n| self.to_world(n))
+    }
 }
 
-/*
+// non-uniform scaling around the origin. factors must be positive
 pub struct Scale {
     pub hit: Box<dyn Hittable>,
-    pub offset: Vec3,
+    pub factors: Vec3,
 }
 
 impl Scale {
-    pub fn new(hit: impl Hittable + 'static, offset: Vec3) -> Self {
-        Self { hit: Box::new(hit), offset }
+    pub fn new(hit: impl Hittable + 'static, factors: Vec3) -> Self {
+        Self {
+            hit: Box::new(hit),
+            factors,
+        }
+    }
+}
+
+impl Scale {
+    fn inverse(&self) -> Vec3 {
+        Vec3::new(
+            1.0 / self.factors.x,
+            1.0 / self.factors.y,
+            1.0 / self.factors.z,
+        )
+    }
+
+    fn volume_change(&self) -> f32 {
+        self.factors.x * self.factors.y * self.factors.z
     }
 }
 
 impl Hittable for Scale {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        if let Some(mut rec) = self.hit.hit(&r, t_min, t_max) {
-            rec.set_face_normal(&r, rec.normal);
+        let inv = self.inverse();
+
+        // scaling origin and direction alike keeps t valid in both spaces
+        let scaled_r = Ray::new(r.orig * inv, r.dir * inv, r.time);
+        if let Some(mut rec) = self.hit.hit(&scaled_r, t_min, t_max) {
+            rec.p *= self.factors;
+            rec.normal = (rec.normal * inv).unit_vector();
             Some(rec)
         } else {
             None
@@ -257,13 +393,125 @@ impl Hittable for Scale {
     }
 
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
-        if let Some(output_box) = self.hit.bounding_box(time0, time1) {
-            Some(AABB::new(
-                    output_box.min,
-                    output_box.max * self.offset))
-        } else {
-            None
+        self.hit
+            .bounding_box(time0, time1)
+            .map(|b| AABB::new(b.min * self.factors, b.max * self.factors))
+    }
+
+    // the same change of density and area as a `Transform` has, for a diagonal matrix
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        let inv = self.inverse();
+        let local = v * inv;
+        let pdf = self.hit.pdf_value(orig * inv, local);
+        let stretch = (local.unit_vector() * self.factors).length();
+        pdf * stretch.powi(3) / self.volume_change()
+    }
+
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.hit.random(orig * self.inverse(), sampler) * self.factors
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.hit.is_area_light()
+    }
+
+    fn area_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        let mut inner = vec![];
+        self.hit.area_lights(&mut inner);
+        for light in inner {
+            lights.push(Arc::new(Scale::new(light, self.factors)));
+        }
+    }
+
+    fn area(&self) -> f32 {
+        let volume = self.volume_change();
+        match self.hit.facing() {
+            Some(normal) => self.hit.area() * volume * (normal * self.inverse()).length(),
+            None => self.hit.area() * volume.powf(2.0 / 3.0),
+        }
+    }
+
+    fn facing(&self) -> Option<Vec3> {
+        self.hit
+            .facing()
+            .map(|n| (n * self.inverse()).unit_vector())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aarect::{AARect, Plane};
+    use crate::material::DiffuseLight;
+    use crate::sampler::SamplerKind;
+    use crate::texture::SolidColorTexture;
+
+    fn light() -> DiffuseLight<SolidColorTexture> {
+        DiffuseLight::new(SolidColorTexture::new(Color::new(4.0, 4.0, 4.0)))
+    }
+
+    // a unit square facing down at y = 1
+    fn square() -> AARect<DiffuseLight<SolidColorTexture>> {
+        AARect::new(Plane::XZ, light(), 0.0, 1.0, 0.0, 1.0, 1.0)
+    }
+
+    fn nested(object: impl Hittable + 'static) -> HittableList {
+        let mut list = HittableList::new();
+        list.push(object);
+        list
+    }
+
+    #[test]
+    fn finds_lights_inside_transforms() {
+        let mut world = HittableList::new();
+        world.push(Translate::new(nested(square()), Vec3::new(3.0, 0.0, 0.0)));
+        world.push(Scale::new(nested(square()), Vec3::new(2.0, 3.0, 4.0)));
+
+        let mut lights = vec![];
+        world.area_lights(&mut lights);
+        assert_eq!(lights.len(), 2);
+
+        let mut sampler = SamplerKind::Random.create(1, 0);
+        sampler.start_sample(0, 0, 0);
+        let p = Point3::new(0.5, -1.0, 0.5);
+        for light in &lights {
+            let dir = light.random(p, &mut *sampler);
+            let pdf = light.pdf_value(p, dir);
+            assert!(
+                pdf.is_finite() && pdf > 0.0,
+                "pdf {} towards {:?}",
+                pdf,
+                dir
+            );
+        }
+    }
+
+    // a scaled square is just a bigger rectangle, and must be sampled like one
+    #[test]
+    fn scaled_light_densities_match_plain_shape() {
+        let factors = Vec3::new(2.0, 3.0, 4.0);
+        let plain = AARect::new(Plane::XZ, light(), 0.0, 2.0, 0.0, 4.0, 3.0);
+        let scaled = Scale::new(square(), factors);
+        let inv = Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z);
+        let transformed = Transform::new(square(), Matrix4::scale(inv));
+
+        let mut sampler = SamplerKind::Random.create(1, 0);
+        sampler.start_sample(0, 0, 0);
+        let p = Point3::new(0.5, -1.0, 0.5);
+        for shape in [&scaled as &dyn Hittable, &transformed] {
+            for _ in 0..16 {
+                let dir = shape.random(p, &mut *sampler);
+                let expected = plain.pdf_value(p, dir);
+                let pdf = shape.pdf_value(p, dir);
+                assert!(expected > 0.0);
+                assert!(
+                    (pdf - expected).abs() <= 1e-3 * expected,
+                    "pdf {} towards {:?}, the plain rectangle has {}",
+                    pdf,
+                    dir,
+                    expected
+                );
+            }
         }
     }
 }
-*/
@@ -2,6 +2,7 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::*;
 
 // https://www.scratchapixel.com/lessons/3d-basic-rendering/ray-tracing-rendering-a-triangle/ray-triangle-intersection-geometric-solution
@@ -82,14 +83,54 @@ impl<M: Sync + Send + Material + 'static> Hittable for Triangle<M> {
     }
 
     fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        // padded like the rects', so triangles lying flat along an axis still have a volume
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
         Some(AABB::new(
             Vec3::new(
                 self.v0.x.min(self.v1.x.min(self.v2.x)),
                 self.v0.y.min(self.v1.y.min(self.v2.y)),
-                self.v0.z.min(self.v1.z.min(self.v2.z))),
+                self.v0.z.min(self.v1.z.min(self.v2.z)),
+            ) - pad,
             Vec3::new(
                 self.v0.x.max(self.v1.x.max(self.v2.x)),
                 self.v0.y.max(self.v1.y.max(self.v2.y)),
-                self.v0.z.max(self.v1.z.max(self.v2.z)))))
+                self.v0.z.max(self.v1.z.max(self.v2.z)),
+            ) + pad,
+        ))
+    }
+
+    fn pdf_value(&self, orig: Point3, v: Vec3) -> f32 {
+        if let Some(hit) = self.hit(&Ray::new(orig, v, 0.0), 0.001, std::f32::INFINITY) {
+            let area = self.area();
+            let distance_squared = hit.t.powi(2) * v.length_squared();
+            let cosine = v.dot(self.normal).abs() / v.length();
+
+            return distance_squared / (cosine * area);
+        }
+
+        0.0
+    }
+
+    // uniform over the area: the square root keeps points from bunching up at v0
+    fn random(&self, orig: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u, v) = sampler.get_2d();
+        let su = u.sqrt();
+        let (b1, b2) = (su * (1.0 - v), su * v);
+        let random_point = self.v0 + b1 * (self.v1 - self.v0) + b2 * (self.v2 - self.v0);
+
+        random_point - orig
+    }
+
+    fn is_area_light(&self) -> bool {
+        self.material.is_emitter()
+    }
+
+    fn area(&self) -> f32 {
+        0.5 * (self.v1 - self.v0).cross(self.v2 - self.v0).length()
+    }
+
+    // hits keep the geometric normal, so emitters only light the side it points to
+    fn facing(&self) -> Option<Vec3> {
+        Some(self.normal)
     }
 }
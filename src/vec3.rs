@@ -1,6 +1,7 @@
 use std::ops;
 
-use rand;
+use crate::rng;
+
 use rand::prelude::*;
 
 #[derive(Debug, Clone, Copy)]
@@ -165,14 +166,14 @@ impl Vec3 {
 
     pub fn random() -> Self {
         Self {
-            x: rand::random(),
-            y: rand::random(),
-            z: rand::random(),
+            x: rng::random(),
+            y: rng::random(),
+            z: rng::random(),
         }
     }
 
     pub fn random_range(min: f32, max: f32) -> Self {
-        let mut rng = thread_rng();
+        let mut rng = rng::local();
         Self {
             x: rng.gen_range(min..max),
             y: rng.gen_range(min..max),
@@ -181,7 +182,7 @@ impl Vec3 {
     }
 
     pub fn random_range_i32(min: i32, max: i32) -> Self {
-        let mut rng = thread_rng();
+        let mut rng = rng::local();
         Self {
             x: rng.gen_range(min..max) as f32,
             y: rng.gen_range(min..max) as f32,
@@ -227,7 +228,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Self {
-        let mut rng = thread_rng();
+        let mut rng = rng::local();
         let mut p;
         loop {
             p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
@@ -274,7 +275,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_sphere() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::local();
         let unit = Vec3::new(1.0, 1.0, 1.0);
         loop {
             let p = 2.0 * Vec3::new(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>()) - unit;
@@ -294,8 +295,8 @@ impl Vec3 {
     }
 
     pub fn random_cosine_dir() -> Self {
-        let r1 = rand::random::<f32>();
-        let r2 = rand::random::<f32>();
+        let r1 = rng::random::<f32>();
+        let r2 = rng::random::<f32>();
 
         let z = (1.0 - r2).sqrt();
 
@@ -306,6 +307,11 @@ impl Vec3 {
         Self { x, y, z }
     }
 
+    // relative luminance of a linear Rec. 709 color
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
     pub fn is_nan(&self) -> bool {
         if self.x.is_nan() {
             return true;